@@ -0,0 +1,61 @@
+//! rough timing of tree construction and queries, run with
+//! `cargo run --release --no-default-features --example construction_bench`
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use glam::Vec2;
+
+use quadtree::utils::positive_rand_range_vec2;
+use quadtree::BarnesHutWrapper;
+use quadtree::BoundingBox;
+use quadtree::Particle;
+use quadtree::QuadTree;
+
+const ROUNDS: u32 = 20;
+
+fn main() {
+    let dimensions = BoundingBox::build(Vec2::ZERO, Vec2::new(1920., 1080.));
+    for count in [1_000, 10_000, 100_000] {
+        let particles: Vec<Particle> = (0..count)
+            .map(|_| {
+                Particle::new(positive_rand_range_vec2(dimensions.max), Vec2::ZERO, fastrand::f32() * 100.)
+            })
+            .collect();
+        let mut tree = QuadTree::build(3, dimensions);
+
+        let start = Instant::now();
+        (0..ROUNDS).for_each(|_| {
+            tree.construct_tree(black_box(&particles));
+        });
+        let construct = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        (0..ROUNDS).for_each(|_| {
+            tree.construct_tree_morton(black_box(&particles));
+        });
+        let construct_morton = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        let mut barnes_hut = BarnesHutWrapper::new();
+        (0..ROUNDS).for_each(|_| {
+            barnes_hut.build_hierarchy(&tree, black_box(&particles));
+        });
+        let hierarchy = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        let mut found = 0;
+        particles.iter().take(1000).for_each(|particle| {
+            let range = BoundingBox::build(
+                particle.position - Vec2::splat(50.),
+                particle.position + Vec2::splat(50.),
+            );
+            found += tree.query_range(black_box(&range)).len();
+        });
+        let query = start.elapsed();
+
+        println!(
+            "{count:>7} particles: construct {construct:>10.2?}  morton {construct_morton:>10.2?}  barnes-hut {hierarchy:>10.2?}  1000 queries {query:>10.2?} ({found} hits)"
+        );
+    }
+}
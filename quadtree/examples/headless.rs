@@ -0,0 +1,32 @@
+//! runs the barnes-hut simulation without a window and logs the tree stats
+//! every frame, handy for tuning the leaf capacity. run with
+//! `cargo run --release --no-default-features --example headless -- [leaf_capacity] [frames]`
+
+use std::env;
+use std::time::Instant;
+
+use quadtree::State;
+
+const FRAME_TIME: f32 = 1. / 60.;
+
+fn main() {
+    let mut args = env::args().skip(1).map(|arg| arg.parse::<usize>().expect("arguments should be numbers"));
+    let leaf_capacity = args.next().unwrap_or(3);
+    let frames = args.next().unwrap_or(100);
+
+    let mut state = State::build(1920, 1080);
+    state.quadtree.leaf_capacity = leaf_capacity;
+    state.init();
+
+    let start = Instant::now();
+    (0..frames).for_each(|_| {
+        state.update_barnes_hut(FRAME_TIME);
+        eprintln!("{}", state.quadtree.stats());
+    });
+    let elapsed = start.elapsed();
+
+    println!(
+        "leaf capacity {leaf_capacity}: {frames} frames in {elapsed:?}, {:?} per frame",
+        elapsed / frames.max(1) as u32
+    );
+}
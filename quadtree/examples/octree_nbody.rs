@@ -0,0 +1,91 @@
+//! headless 3D n-body run on the octree, using the same barnes-hut code as
+//! the 2D simulation. run with
+//! `cargo run --release --no-default-features --example octree_nbody -- [particles] [frames]`
+
+use std::env;
+use std::time::Instant;
+
+use glam::Vec3;
+
+use quadtree::BarnesHutWrapper;
+use quadtree::BoundingBox3;
+use quadtree::ForceParameters;
+use quadtree::MassPoint;
+use quadtree::Octree;
+use quadtree::PositionSpatial;
+
+const FRAME_TIME: f32 = 1. / 60.;
+const SIZE: f32 = 1000.;
+
+#[derive(Debug, Clone, Copy)]
+struct Particle3 {
+    position: Vec3,
+    velocity: Vec3,
+    mass: f32,
+}
+
+impl PositionSpatial for Particle3 {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+impl MassPoint for Particle3 {
+    type Vector = Vec3;
+
+    fn mass_position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn mass(&self) -> f32 {
+        self.mass
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1).map(|arg| arg.parse::<usize>().expect("arguments should be numbers"));
+    let count = args.next().unwrap_or(10_000);
+    let frames = args.next().unwrap_or(100);
+
+    let parameters = ForceParameters { gravity: 1e2, epsilon_squared: 10., theta: 2_f32.sqrt() / 2. };
+    let mut particles: Vec<Particle3> = (0..count)
+        .map(|_| Particle3 {
+            position: Vec3::new(fastrand::f32(), fastrand::f32(), fastrand::f32()) * SIZE,
+            velocity: (Vec3::new(fastrand::f32(), fastrand::f32(), fastrand::f32()) * 2. - Vec3::ONE) * 50.,
+            mass: fastrand::f32() * 100.,
+        })
+        .collect();
+
+    // items leaving the box land in overflow, so the bounds don't need to
+    // follow them
+    let mut tree = Octree::build(8, BoundingBox3::build(Vec3::ZERO, Vec3::splat(SIZE)));
+    let mut barnes_hut = BarnesHutWrapper::new();
+    let start = Instant::now();
+    (0..frames).for_each(|frame| {
+        tree.construct_tree(&particles);
+        barnes_hut.build_hierarchy(&tree, &particles);
+        let accelerations: Vec<Vec3> = (0..particles.len())
+            .map(|target_index| barnes_hut.acceleration(&tree, &particles, target_index, parameters))
+            .collect();
+
+        particles.iter_mut().zip(accelerations).for_each(|(particle, acceleration)| {
+            particle.velocity += acceleration * FRAME_TIME;
+            particle.position += particle.velocity * FRAME_TIME;
+        });
+
+        if frame % 10 == 0 {
+            let momentum: Vec3 = particles.iter().map(|particle| particle.velocity * particle.mass).sum();
+            println!(
+                "frame {frame}: {} nodes, {} in overflow, momentum {momentum:.1}",
+                tree.nodes.len(),
+                tree.overflow.len()
+            );
+        }
+    });
+    let elapsed = start.elapsed();
+
+    println!(
+        "{count} particles, {frames} frames in {elapsed:?}, {:?} per frame",
+        elapsed / frames.max(1) as u32
+    );
+}
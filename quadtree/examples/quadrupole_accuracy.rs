@@ -0,0 +1,84 @@
+//! compares barnes-hut with and without quadrupole moments against direct
+//! summation over a range of theta, printing the relative force error and the
+//! time the force pass took. run with
+//! `cargo run --release --no-default-features --example quadrupole_accuracy -- [particles] [frames]`
+
+use std::env;
+use std::time::Instant;
+
+use glam::Vec2;
+
+use quadtree::BarnesHutWrapper;
+use quadtree::ForceParameters;
+use quadtree::State;
+
+const FRAME_TIME: f32 = 1. / 60.;
+const THETAS: [f32; 5] = [0.3, 0.5, 0.707, 1., 1.5];
+
+fn main() {
+    let mut args = env::args().skip(1).map(|arg| arg.parse::<usize>().expect("arguments should be numbers"));
+    let particle_count = args.next().unwrap_or(5000);
+    let frames = args.next().unwrap_or(50);
+
+    // let the simulation clump the particles up a bit first
+    let mut state = State::build(1920, 1080);
+    state.config.starting_spawn = particle_count;
+    state.init();
+    (0..frames).for_each(|_| {
+        state.update_barnes_hut(FRAME_TIME);
+    });
+    state.quadtree.construct_tree(&state.particles);
+
+    let parameters = state.config.force_parameters();
+    let start = Instant::now();
+    let direct: Vec<Vec2> =
+        (0..state.particles.len()).map(|target_index| direct_sum(&state, target_index, parameters)).collect();
+    println!("{} particles, direct summation {:?}", state.particles.len(), start.elapsed());
+    println!("{:>6} {:>10} {:>12} {:>12} {:>12}", "theta", "moments", "median err", "p99 err", "forces");
+
+    THETAS.into_iter().for_each(|theta| {
+        [false, true].into_iter().for_each(|use_quadrupole| {
+            let parameters = ForceParameters { theta, ..parameters };
+            let start = Instant::now();
+            let mut barnes_hut = BarnesHutWrapper::new().with_quadrupole(use_quadrupole);
+            barnes_hut.build_hierarchy(&state.quadtree, &state.particles);
+            let approximate: Vec<Vec2> = (0..state.particles.len())
+                .map(|target_index| {
+                    barnes_hut.acceleration(&state.quadtree, &state.particles, target_index, parameters)
+                })
+                .collect();
+            let elapsed = start.elapsed();
+
+            let mut errors: Vec<f32> = approximate
+                .iter()
+                .zip(&direct)
+                .filter(|(_, exact)| exact.length_squared() > 0.)
+                .map(|(approximate, exact)| (*approximate - *exact).length() / exact.length())
+                .collect();
+            errors.sort_by(f32::total_cmp);
+            let percentile = |fraction: f32| errors[((errors.len() - 1) as f32 * fraction) as usize];
+
+            let moments = if use_quadrupole { "quadrupole" } else { "monopole" };
+            println!(
+                "{theta:>6} {moments:>10} {:>12.2e} {:>12.2e} {:>12}",
+                percentile(0.5),
+                percentile(0.99),
+                format!("{elapsed:.2?}")
+            );
+        });
+    });
+}
+
+/// the exact sum barnes-hut approximates, with the same epsilon cutoff
+fn direct_sum(state: &State, target_index: usize, parameters: ForceParameters) -> Vec2 {
+    let target = &state.particles[target_index];
+    state.particles.iter().enumerate().fold(Vec2::ZERO, |acceleration, (other_index, other)| {
+        let pointing = other.position - target.position;
+        let sq_radius = pointing.length_squared();
+        if other_index == target_index || sq_radius < parameters.epsilon_squared {
+            return acceleration;
+        }
+
+        acceleration + pointing.normalize() * parameters.gravity * other.mass / sq_radius
+    })
+}
@@ -0,0 +1,50 @@
+//! builds the tree over the same clustered particles with every split policy
+//! and prints the stats side by side. run with
+//! `cargo run --release --no-default-features --example split_policies -- [frames]`
+
+use std::env;
+use std::time::Instant;
+
+use quadtree::BarnesHutWrapper;
+use quadtree::SplitPolicy;
+use quadtree::State;
+
+const FRAME_TIME: f32 = 1. / 60.;
+
+fn main() {
+    let frames =
+        env::args().nth(1).map_or(200, |arg| arg.parse::<usize>().expect("frames should be a number"));
+
+    // let the simulation pull the particles in around the heavy central body
+    let mut state = State::build(1920, 1080);
+    state.init();
+    (0..frames).for_each(|_| {
+        state.update_barnes_hut(FRAME_TIME);
+    });
+
+    [SplitPolicy::Center, SplitPolicy::Median, SplitPolicy::MassWeighted].into_iter().for_each(
+        |split_policy| {
+            state.quadtree.split_policy = split_policy;
+
+            let start = Instant::now();
+            state.quadtree.construct_tree_weighted(&state.particles);
+            let construct = start.elapsed();
+
+            let start = Instant::now();
+            let mut barnes_hut = BarnesHutWrapper::new();
+            barnes_hut.build_hierarchy(&state.quadtree, &state.particles);
+            (0..state.particles.len()).for_each(|target_index| {
+                barnes_hut.acceleration(
+                    &state.quadtree,
+                    &state.particles,
+                    target_index,
+                    state.config.force_parameters(),
+                );
+            });
+            let forces = start.elapsed();
+
+            println!("{split_policy:?}: construct {construct:?} forces {forces:?}");
+            println!("    {}", state.quadtree.stats());
+        },
+    );
+}
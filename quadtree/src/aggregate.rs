@@ -0,0 +1,151 @@
+use std::ops::Range;
+
+use glam::Vec2;
+
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+use crate::traversal::TreeTraversal;
+use crate::utils::BoundingBox;
+
+/// the shape of a flat tree (`QuadTree`, `Octree`) as seen by anything that
+/// walks it node by node. node 0 is the root
+pub trait TreeHierarchy {
+    type Boundary;
+
+    fn node_count(&self) -> usize;
+
+    fn node_boundary(&self, node_index: usize) -> Self::Boundary;
+
+    /// the root is depth 0
+    fn node_depth(&self, node_index: usize) -> usize;
+
+    /// `None` for leaves
+    fn node_children(&self, node_index: usize) -> Option<Range<usize>>;
+
+    /// items stored directly in the node, always empty for stems
+    fn node_items(&self, node_index: usize) -> &[usize];
+
+    /// widest side of the node's boundary
+    fn node_size(&self, node_index: usize) -> f32;
+
+    /// items outside of every node
+    fn overflow_items(&self) -> &[usize];
+}
+
+impl TreeHierarchy for QuadTree {
+    type Boundary = BoundingBox;
+
+    fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn node_boundary(&self, node_index: usize) -> BoundingBox {
+        self.nodes[node_index].boundary
+    }
+
+    fn node_depth(&self, node_index: usize) -> usize {
+        self.nodes[node_index].depth
+    }
+
+    fn node_children(&self, node_index: usize) -> Option<Range<usize>> {
+        self.nodes[node_index].leaves.map(|leaf_start| leaf_start..(leaf_start + Self::STEM_LEAF_COUNT))
+    }
+
+    fn node_items(&self, node_index: usize) -> &[usize] {
+        self.leaf_items(node_index)
+    }
+
+    fn node_size(&self, node_index: usize) -> f32 {
+        self.nodes[node_index].boundary.max_dimension()
+    }
+
+    fn overflow_items(&self) -> &[usize] {
+        &self.overflow
+    }
+}
+
+/// a per node summary computed bottom-up in one pass: leaves summarize their
+/// items, stems summarize their children. overflow items aren't part of any
+/// node so they aren't in any summary either
+pub trait NodeAggregate<T>: Clone + Default {
+    /// `item_indices` are the leaf's items, indices into `items`
+    fn from_items(items: &[T], item_indices: &[usize]) -> Self;
+
+    /// `children` are the already computed summaries of a stem's children
+    fn combine(children: &[Self]) -> Self;
+}
+
+/// one `A` per node of `tree`, lined up with its nodes
+pub fn aggregate<Tree, T, A>(tree: &Tree, items: &[T]) -> Vec<A>
+where
+    Tree: TreeHierarchy,
+    A: NodeAggregate<T>,
+{
+    let mut values = Vec::new();
+    aggregate_into(tree, items, &mut values);
+    values
+}
+
+/// same as `aggregate`, but reuses the allocation of `values`. nodes that
+/// aren't reachable from the root (detached groups waiting for reuse) are left
+/// at whatever they were
+pub fn aggregate_into<Tree, T, A>(tree: &Tree, items: &[T], values: &mut Vec<A>)
+where
+    Tree: TreeHierarchy,
+    A: NodeAggregate<T>,
+{
+    values.resize_with(tree.node_count(), A::default);
+    // children always come out before their parent
+    tree.post_order().for_each(|node| {
+        values[node.index] = match node.children {
+            Some(children) => A::combine(&values[children]),
+            None => A::from_items(items, node.items),
+        };
+    });
+}
+
+/// how many items are below the node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ItemCount(pub usize);
+
+impl<T> NodeAggregate<T> for ItemCount {
+    fn from_items(_items: &[T], item_indices: &[usize]) -> Self {
+        ItemCount(item_indices.len())
+    }
+
+    fn combine(children: &[Self]) -> Self {
+        ItemCount(children.iter().map(|child| child.0).sum())
+    }
+}
+
+/// tightest box around the positions of the items below the node, `None` if
+/// there aren't any. usually a lot smaller than the node's boundary. max is
+/// nudged up to the next float so `BoundingBox::contains`, which leaves max
+/// out, still holds the extreme items
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContentBounds(pub Option<BoundingBox>);
+
+impl<T> NodeAggregate<T> for ContentBounds
+where
+    T: PositionPlanar,
+{
+    fn from_items(items: &[T], item_indices: &[usize]) -> Self {
+        item_indices.iter().fold(ContentBounds(None), |bounds, &item_index| {
+            let position = items[item_index].position();
+            let past = Vec2::new(position.x.next_up(), position.y.next_up());
+            ContentBounds(merge_bounds(bounds.0, Some(BoundingBox::build(position, past))))
+        })
+    }
+
+    fn combine(children: &[Self]) -> Self {
+        ContentBounds(children.iter().fold(None, |bounds, child| merge_bounds(bounds, child.0)))
+    }
+}
+
+/// smallest box around both, either of them can be missing
+pub(crate) fn merge_bounds(a: Option<BoundingBox>, b: Option<BoundingBox>) -> Option<BoundingBox> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(BoundingBox::build(a.min.min(b.min), a.max.max(b.max))),
+        (a, b) => a.or(b),
+    }
+}
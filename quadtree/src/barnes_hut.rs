@@ -1,69 +1,400 @@
+use std::ops::Add;
+use std::ops::AddAssign;
+use std::ops::Div;
+use std::ops::Mul;
+use std::ops::Sub;
+
+use glam::Mat2;
+use glam::Mat3;
 use glam::Vec2;
+use glam::Vec3;
 
-use crate::quadtree::QuadTree;
-use crate::state::Particle;
+use crate::aggregate::aggregate_into;
+use crate::aggregate::NodeAggregate;
+use crate::aggregate::TreeHierarchy;
+use crate::traversal::TreeTraversal;
+use crate::traversal::Visit;
 use crate::utils::EPSILON;
 
-#[derive(Clone, Copy)]
-pub struct BarnesHutNode {
+/// the bit of vector math barnes-hut needs, so the same code runs on `Vec2`
+/// and `Vec3`
+pub trait MassVector:
+    Copy
+    + Default
+    + Add<Output = Self>
+    + AddAssign
+    + Sub<Output = Self>
+    + Mul<f32, Output = Self>
+    + Div<f32, Output = Self>
+{
+    // symmetric matrix holding a quadrupole moment
+    type Tensor: Copy + Add<Output = Self::Tensor> + AddAssign + Mul<Self, Output = Self>;
+
+    const ZERO: Self;
+    const TENSOR_ZERO: Self::Tensor;
+
+    fn length_squared(self) -> f32;
+
+    fn normalize(self) -> Self;
+
+    fn dot(self, other: Self) -> f32;
+
+    /// mass * (3 x x^T - |x|^2 I), the traceless quadrupole of a point mass
+    /// sitting at this offset from the expansion center
+    fn quadrupole(self, mass: f32) -> Self::Tensor;
+}
+
+impl MassVector for Vec2 {
+    type Tensor = Mat2;
+
+    const ZERO: Self = Vec2::ZERO;
+    const TENSOR_ZERO: Mat2 = Mat2::ZERO;
+
+    fn length_squared(self) -> f32 {
+        Vec2::length_squared(self)
+    }
+
+    fn normalize(self) -> Self {
+        Vec2::normalize(self)
+    }
+
+    fn dot(self, other: Self) -> f32 {
+        Vec2::dot(self, other)
+    }
+
+    fn quadrupole(self, mass: f32) -> Mat2 {
+        let outer = Mat2::from_cols(self * (3. * self.x), self * (3. * self.y));
+        (outer - Mat2::from_diagonal(Vec2::splat(self.length_squared()))) * mass
+    }
+}
+
+impl MassVector for Vec3 {
+    type Tensor = Mat3;
+
+    const ZERO: Self = Vec3::ZERO;
+    const TENSOR_ZERO: Mat3 = Mat3::ZERO;
+
+    fn length_squared(self) -> f32 {
+        Vec3::length_squared(self)
+    }
+
+    fn normalize(self) -> Self {
+        Vec3::normalize(self)
+    }
+
+    fn dot(self, other: Self) -> f32 {
+        Vec3::dot(self, other)
+    }
+
+    fn quadrupole(self, mass: f32) -> Mat3 {
+        let outer = Mat3::from_cols(self * (3. * self.x), self * (3. * self.y), self * (3. * self.z));
+        (outer - Mat3::from_diagonal(Vec3::splat(self.length_squared()))) * mass
+    }
+}
+
+/// anything with a mass sitting in the same space as the tree
+pub trait MassPoint {
+    type Vector: MassVector;
+
+    fn mass_position(&self) -> Self::Vector;
+
+    fn mass(&self) -> f32;
+}
+
+/// the constants of the force calculation
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ForceParameters {
+    pub gravity: f32,
+    // pairs closer than this (squared) don't pull on each other at all
+    pub epsilon_squared: f32,
+    // node size over distance below which a whole node counts as one mass
+    pub theta: f32,
+}
+
+/// total mass and center of mass of everything below a node, the
+/// `NodeAggregate` barnes-hut runs on
+#[derive(Clone, Copy, Default)]
+pub struct BarnesHutNode<V = Vec2> {
     pub mass: f32,
-    pub mass_center: Vec2,
+    pub mass_center: V,
 }
 
-impl BarnesHutNode {
-    pub fn build(mass: f32, mass_center: Vec2) -> Self {
+impl<V> BarnesHutNode<V>
+where
+    V: MassVector,
+{
+    pub fn build(mass: f32, mass_center: V) -> Self {
         BarnesHutNode { mass, mass_center }
     }
+
+    /// `mass_averaged_position` is the sum of position * mass, which gets
+    /// turned into the center unless there is no mass to divide by
+    fn from_sums(mass: f32, mut mass_averaged_position: V) -> Self {
+        if mass > EPSILON {
+            mass_averaged_position = mass_averaged_position / mass;
+        }
+
+        BarnesHutNode::build(mass, mass_averaged_position)
+    }
 }
 
-pub struct BarnesHutWrapper {
-    pub barnes_hut_data: Vec<Option<BarnesHutNode>>,
+impl<P> NodeAggregate<P> for BarnesHutNode<P::Vector>
+where
+    P: MassPoint,
+{
+    fn from_items(items: &[P], item_indices: &[usize]) -> Self {
+        let mut mass = 0.;
+        let mut mass_averaged_position = P::Vector::ZERO;
+        item_indices.iter().for_each(|&particle_index| {
+            let particle = &items[particle_index];
+            mass += particle.mass();
+            mass_averaged_position += particle.mass_position() * particle.mass();
+        });
+
+        Self::from_sums(mass, mass_averaged_position)
+    }
+
+    fn combine(children: &[Self]) -> Self {
+        let mut mass = 0.;
+        let mut mass_averaged_position = P::Vector::ZERO;
+        children.iter().for_each(|child| {
+            mass += child.mass;
+            mass_averaged_position += child.mass_center * child.mass;
+        });
+
+        Self::from_sums(mass, mass_averaged_position)
+    }
 }
 
-impl BarnesHutWrapper {
-    pub fn new() -> BarnesHutWrapper {
-        BarnesHutWrapper { barnes_hut_data: Vec::new() }
+/// `BarnesHutNode` plus the quadrupole moment about its center of mass
+#[derive(Clone, Copy)]
+pub struct QuadrupoleNode<V = Vec2>
+where
+    V: MassVector,
+{
+    pub monopole: BarnesHutNode<V>,
+    pub quadrupole: V::Tensor,
+}
+
+// glam's matrices default to the identity
+impl<V> Default for QuadrupoleNode<V>
+where
+    V: MassVector,
+{
+    fn default() -> Self {
+        QuadrupoleNode { monopole: BarnesHutNode::default(), quadrupole: V::TENSOR_ZERO }
     }
+}
+
+impl<P> NodeAggregate<P> for QuadrupoleNode<P::Vector>
+where
+    P: MassPoint,
+{
+    fn from_items(items: &[P], item_indices: &[usize]) -> Self {
+        let monopole: BarnesHutNode<P::Vector> = NodeAggregate::from_items(items, item_indices);
+        let mut quadrupole = P::Vector::TENSOR_ZERO;
+        item_indices.iter().for_each(|&particle_index| {
+            let particle = &items[particle_index];
+            quadrupole += (particle.mass_position() - monopole.mass_center).quadrupole(particle.mass());
+        });
 
-    pub fn build_hierarchy(&mut self, tree: &QuadTree, particles: &[Particle]) {
-        self.init_vector(tree);
-        self.builder(QuadTree::ROOT_INDEX, tree, particles);
+        QuadrupoleNode { monopole, quadrupole }
     }
 
-    fn builder(&mut self, target_index: usize, tree: &QuadTree, particles: &[Particle]) -> BarnesHutNode {
+    /// parallel axis theorem, each child's moment gets moved from its own
+    /// center of mass over to the combined one
+    fn combine(children: &[Self]) -> Self {
         let mut mass = 0.;
-        let mut mass_averaged_position = Vec2::ZERO;
-
-        if let Some(leaf_start) = tree.nodes[target_index].leaves {
-            (leaf_start..(leaf_start + QuadTree::STEM_LEAF_COUNT)).for_each(|leaf| {
-                let leaf_data = self.builder(leaf, tree, particles);
-                mass += leaf_data.mass;
-                mass_averaged_position += leaf_data.mass_center * leaf_data.mass;
-            });
+        let mut mass_averaged_position = P::Vector::ZERO;
+        children.iter().for_each(|child| {
+            mass += child.monopole.mass;
+            mass_averaged_position += child.monopole.mass_center * child.monopole.mass;
+        });
+        let monopole = BarnesHutNode::from_sums(mass, mass_averaged_position);
+
+        let mut quadrupole = P::Vector::TENSOR_ZERO;
+        children.iter().for_each(|child| {
+            let offset = child.monopole.mass_center - monopole.mass_center;
+            quadrupole += child.quadrupole + offset.quadrupole(child.monopole.mass);
+        });
+
+        QuadrupoleNode { monopole, quadrupole }
+    }
+}
+
+pub struct BarnesHutWrapper<V = Vec2>
+where
+    V: MassVector,
+{
+    // lines up with the tree's nodes
+    pub barnes_hut_data: Vec<BarnesHutNode<V>>,
+    // also lines up with the tree's nodes, but stays empty unless
+    // `with_quadrupole` turned them on
+    pub quadrupoles: Vec<V::Tensor>,
+    pub use_quadrupole: bool,
+}
+
+impl<V> Default for BarnesHutWrapper<V>
+where
+    V: MassVector,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> BarnesHutWrapper<V>
+where
+    V: MassVector,
+{
+    pub fn new() -> BarnesHutWrapper<V> {
+        BarnesHutWrapper { barnes_hut_data: Vec::new(), quadrupoles: Vec::new(), use_quadrupole: false }
+    }
+
+    /// adds a quadrupole correction to every node that is far enough away to
+    /// be approximated, a lot more accurate for the same theta at the cost of
+    /// a slower build and force pass
+    pub fn with_quadrupole(mut self, use_quadrupole: bool) -> Self {
+        self.use_quadrupole = use_quadrupole;
+        self
+    }
+
+    pub fn build_hierarchy<Tree, P>(&mut self, tree: &Tree, particles: &[P])
+    where
+        Tree: TreeHierarchy,
+        P: MassPoint<Vector = V>,
+    {
+        if !self.use_quadrupole {
+            self.quadrupoles.clear();
+            aggregate_into(tree, particles, &mut self.barnes_hut_data);
+            return;
         }
-        else if let Some(list_node_index) = tree.nodes[target_index].data_head {
-            tree.node_pointers[list_node_index].iter().for_each(|&particle_index| {
-                let particle = &particles[particle_index];
-                mass += particle.mass;
-                mass_averaged_position += particle.position * particle.mass;
-            });
+
+        let mut nodes: Vec<QuadrupoleNode<V>> = Vec::new();
+        aggregate_into(tree, particles, &mut nodes);
+        self.barnes_hut_data = nodes.iter().map(|node| node.monopole).collect();
+        self.quadrupoles = nodes.into_iter().map(|node| node.quadrupole).collect();
+    }
+
+    /// barnes-hut approximation of the acceleration on `particles[target_index]`
+    /// from everything else in the tree, plus the exact pull of the overflow
+    pub fn acceleration<Tree, P>(
+        &self, tree: &Tree, particles: &[P], target_index: usize, parameters: ForceParameters,
+    ) -> V
+    where
+        Tree: TreeHierarchy,
+        P: MassPoint<Vector = V>,
+    {
+        let mut acceleration = V::ZERO;
+        if self.quadrupoles.is_empty() {
+            self.accumulate_force(target_index, tree, particles, parameters, &mut acceleration);
+        }
+        else {
+            self.accumulate_quadrupole_force(target_index, tree, particles, parameters, &mut acceleration);
         }
 
-        self.update_node(target_index, mass, mass_averaged_position)
+        // particles that escaped the tree don't show up in the hierarchy, so
+        // they are summed directly
+        tree.overflow_items().iter().for_each(|&other_index| {
+            acceleration += pairwise_acceleration(particles, target_index, other_index, parameters);
+        });
+
+        acceleration
     }
 
-    fn init_vector(&mut self, tree: &QuadTree) {
-        self.barnes_hut_data.resize_with(tree.nodes.len(), || None);
+    fn accumulate_force<Tree, P>(
+        &self, target_index: usize, tree: &Tree, particles: &[P], parameters: ForceParameters,
+        acceleration: &mut V,
+    ) where
+        Tree: TreeHierarchy,
+        P: MassPoint<Vector = V>,
+    {
+        let target_particle = &particles[target_index];
+        tree.walk_with(0, |node| {
+            let node_data = self.barnes_hut_data[node.index];
+
+            let pointing = node_data.mass_center - target_particle.mass_position();
+            let sq_radius = pointing.length_squared();
+            if sq_radius < parameters.epsilon_squared {
+                return Visit::Prune;
+            }
+
+            let s_over_d = tree.node_size(node.index) / sq_radius.sqrt();
+            if s_over_d >= parameters.theta && !node.is_leaf() {
+                return Visit::Descend;
+            }
+
+            let force_magnitude = parameters.gravity * target_particle.mass() * node_data.mass / sq_radius;
+            *acceleration += pointing.normalize() * force_magnitude / target_particle.mass();
+            Visit::Prune
+        });
     }
 
-    fn update_node(&mut self, target_index: usize, mass: f32, mut mass_center: Vec2) -> BarnesHutNode {
-        if mass > EPSILON {
-            mass_center /= mass;
-        }
+    /// `accumulate_force` with the quadrupole correction on every node far
+    /// enough away to be approximated. leaves that are too close get summed
+    /// item by item instead, a leaf's expansion is useless that close and the
+    /// target would pull on itself through its own leaf
+    fn accumulate_quadrupole_force<Tree, P>(
+        &self, target_index: usize, tree: &Tree, particles: &[P], parameters: ForceParameters,
+        acceleration: &mut V,
+    ) where
+        Tree: TreeHierarchy,
+        P: MassPoint<Vector = V>,
+    {
+        let target_particle = &particles[target_index];
+        tree.walk_with(0, |node| {
+            let node_data = self.barnes_hut_data[node.index];
+
+            let pointing = node_data.mass_center - target_particle.mass_position();
+            let sq_radius = pointing.length_squared();
+            let s_over_d = tree.node_size(node.index) / sq_radius.sqrt();
+            if s_over_d >= parameters.theta {
+                if !node.is_leaf() {
+                    return Visit::Descend;
+                }
+
+                node.items.iter().for_each(|&other_index| {
+                    *acceleration += pairwise_acceleration(particles, target_index, other_index, parameters);
+                });
+                return Visit::Prune;
+            }
 
-        let node = BarnesHutNode::build(mass, mass_center);
-        self.barnes_hut_data[target_index] = Some(node);
+            if sq_radius < parameters.epsilon_squared {
+                return Visit::Prune;
+            }
 
-        node
+            let force_magnitude = parameters.gravity * target_particle.mass() * node_data.mass / sq_radius;
+            *acceleration += pointing.normalize() * force_magnitude / target_particle.mass();
+
+            // gradient of -G (r^T Q r) / (2 |r|^5) with r pointing from the
+            // center of mass to the target, which is -pointing
+            let quadrupole = self.quadrupoles[node.index];
+            let q_pointing = quadrupole * pointing;
+            let radius_5 = sq_radius * sq_radius * sq_radius.sqrt();
+            *acceleration += (pointing * (2.5 * pointing.dot(q_pointing) / sq_radius) - q_pointing)
+                * (parameters.gravity / radius_5);
+            Visit::Prune
+        });
     }
 }
+
+/// exact pull of one particle on another, zero for the particle itself and for
+/// pairs closer than epsilon
+pub(crate) fn pairwise_acceleration<P>(
+    particles: &[P], target_index: usize, other_index: usize, parameters: ForceParameters,
+) -> P::Vector
+where
+    P: MassPoint,
+{
+    let target_particle = &particles[target_index];
+    let other = &particles[other_index];
+    let pointing = other.mass_position() - target_particle.mass_position();
+    let sq_radius = pointing.length_squared();
+    if other_index == target_index || sq_radius < parameters.epsilon_squared {
+        return P::Vector::ZERO;
+    }
+
+    let force_magnitude = parameters.gravity * target_particle.mass() * other.mass() / sq_radius;
+    pointing.normalize() * force_magnitude / target_particle.mass()
+}
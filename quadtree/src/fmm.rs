@@ -0,0 +1,460 @@
+use std::ops::Add;
+use std::ops::AddAssign;
+use std::ops::Mul;
+use std::ops::Sub;
+
+use glam::Vec2;
+
+use crate::aggregate::TreeHierarchy;
+use crate::barnes_hut::pairwise_acceleration;
+use crate::barnes_hut::ForceParameters;
+use crate::barnes_hut::MassPoint;
+use crate::traversal::TreeTraversal;
+use crate::utils::BoundingBox;
+
+/// just enough complex math for the expansions. f64 since the higher order
+/// terms lose too much in f32
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex { re: 0., im: 0. };
+    pub const ONE: Complex = Complex { re: 1., im: 0. };
+
+    pub fn build(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    pub fn from_vec2(point: Vec2) -> Self {
+        Complex::build(point.x as f64, point.y as f64)
+    }
+
+    pub fn conj(self) -> Self {
+        Complex::build(self.re, -self.im)
+    }
+
+    pub fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn recip(self) -> Self {
+        let norm_squared = self.norm_squared();
+        Complex::build(self.re / norm_squared, -self.im / norm_squared)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::build(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, other: Complex) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::build(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::build(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, scale: f64) -> Complex {
+        Complex::build(self.re * scale, self.im * scale)
+    }
+}
+
+/// fast multipole method on the same tree and force law as barnes-hut.
+///
+/// the 1/r potential isn't harmonic in the plane, so instead of the classic
+/// power series in z the expansions run over z and conj(z) together: 1/|z| is
+/// z^-1/2 * conj(z)^-1/2, which splits into a product of two one variable
+/// series. a node's coefficient (k, l) multiplies z^k conj(z)^l, and only
+/// k + l <= order is kept
+pub struct FmmSolver {
+    // highest k + l kept, the error falls off roughly like opening^(order + 1)
+    pub order: usize,
+    // two nodes interact through their expansions once
+    // (radius_a + radius_b) < opening * distance, otherwise they get split
+    pub opening: f32,
+    // (order + 1)(order + 2) / 2 coefficients per node, lined up with the
+    // tree's nodes
+    pub multipoles: Vec<Complex>,
+    pub locals: Vec<Complex>,
+}
+
+impl FmmSolver {
+    pub const DEFAULT_ORDER: usize = 4;
+    pub const DEFAULT_OPENING: f32 = 0.7;
+
+    pub fn build(order: usize) -> Self {
+        FmmSolver { order, opening: Self::DEFAULT_OPENING, multipoles: Vec::new(), locals: Vec::new() }
+    }
+
+    pub fn with_opening(mut self, opening: f32) -> Self {
+        self.opening = opening;
+        self
+    }
+
+    /// every particle's acceleration, the same sum `BarnesHutWrapper` works
+    /// out one particle at a time. `parameters.theta` isn't used, `opening`
+    /// takes its place
+    pub fn accelerations<Tree, P>(
+        &mut self, tree: &Tree, particles: &[P], parameters: ForceParameters,
+    ) -> Vec<Vec2>
+    where
+        Tree: TreeHierarchy<Boundary = BoundingBox>,
+        P: MassPoint<Vector = Vec2>,
+    {
+        let expansion = Expansion::build(self.order);
+        let coefficient_count = tree.node_count() * expansion.terms.len();
+        self.multipoles.clear();
+        self.multipoles.resize(coefficient_count, Complex::ZERO);
+        self.locals.clear();
+        self.locals.resize(coefficient_count, Complex::ZERO);
+        let mut accelerations = vec![Complex::ZERO; particles.len()];
+
+        self.upward_pass(&expansion, tree, particles);
+        self.interact(&expansion, tree, particles, parameters, &mut accelerations);
+        self.downward_pass(&expansion, tree, particles, parameters, &mut accelerations);
+
+        // particles that escaped the tree pull on everything directly, and get
+        // pulled on by everything directly
+        let direct = |target_index, other_index| {
+            Complex::from_vec2(pairwise_acceleration(particles, target_index, other_index, parameters))
+        };
+        let overflow = tree.overflow_items();
+        let mut in_overflow = vec![false; particles.len()];
+        overflow.iter().for_each(|&overflow_index| in_overflow[overflow_index] = true);
+        overflow.iter().for_each(|&overflow_index| {
+            (0..particles.len()).for_each(|other_index| {
+                if !in_overflow[other_index] {
+                    accelerations[other_index] += direct(other_index, overflow_index);
+                }
+                accelerations[overflow_index] += direct(overflow_index, other_index);
+            });
+        });
+
+        accelerations
+            .into_iter()
+            .map(|acceleration| Vec2::new(acceleration.re as f32, acceleration.im as f32))
+            .collect()
+    }
+
+    /// particle to multipole in the leaves, then multipole to multipole up
+    /// into every stem
+    fn upward_pass<Tree, P>(&mut self, expansion: &Expansion, tree: &Tree, particles: &[P])
+    where
+        Tree: TreeHierarchy<Boundary = BoundingBox>,
+        P: MassPoint<Vector = Vec2>,
+    {
+        let size = expansion.terms.len();
+        let mut powers = expansion.powers();
+        let mut child_coefficients = vec![Complex::ZERO; size];
+        // children always come out before their parent
+        tree.post_order().for_each(|node| {
+            let center = Complex::from_vec2(node.boundary.center());
+            let Some(children) = node.children
+            else {
+                let coefficients = &mut self.multipoles[(node.index * size)..((node.index + 1) * size)];
+                node.items.iter().for_each(|&item_index| {
+                    let particle = &particles[item_index];
+                    // stored for -offset, which saves flipping signs in every
+                    // multipole to local
+                    let offset = center - Complex::from_vec2(particle.mass_position());
+                    expansion.fill_powers(offset, &mut powers);
+                    expansion.terms.iter().zip(coefficients.iter_mut()).for_each(|(&(k, l), coefficient)| {
+                        *coefficient += powers.z[k]
+                            * powers.z_conj[l]
+                            * (particle.mass() as f64
+                                * expansion.inverse_factorials[k]
+                                * expansion.inverse_factorials[l]);
+                    });
+                });
+                return;
+            };
+
+            children.for_each(|child| {
+                child_coefficients.copy_from_slice(&self.multipoles[(child * size)..((child + 1) * size)]);
+                let shift = center - Complex::from_vec2(tree.node_boundary(child).center());
+                expansion.fill_powers(shift, &mut powers);
+                let coefficients = &mut self.multipoles[(node.index * size)..((node.index + 1) * size)];
+                expansion.terms.iter().zip(coefficients.iter_mut()).for_each(|(&(k, l), coefficient)| {
+                    (0..=k).for_each(|i| {
+                        (0..=l).for_each(|j| {
+                            *coefficient += child_coefficients[expansion.term_index(i, j)]
+                                * powers.z[k - i]
+                                * powers.z_conj[l - j]
+                                * (expansion.inverse_factorials[k - i] * expansion.inverse_factorials[l - j]);
+                        });
+                    });
+                });
+            });
+        });
+    }
+
+    /// dual tree walk over every (target, source) pair of nodes. well
+    /// separated pairs go through multipole to local, pairs of leaves that
+    /// are too close get summed directly, anything else splits the bigger node
+    fn interact<Tree, P>(
+        &mut self, expansion: &Expansion, tree: &Tree, particles: &[P], parameters: ForceParameters,
+        accelerations: &mut [Complex],
+    ) where
+        Tree: TreeHierarchy<Boundary = BoundingBox>,
+        P: MassPoint<Vector = Vec2>,
+    {
+        let size = expansion.terms.len();
+        let minimum_gap = parameters.epsilon_squared.sqrt();
+        let mut derivatives = expansion.derivatives();
+        let direct = |target_index, source_index| {
+            Complex::from_vec2(pairwise_acceleration(particles, target_index, source_index, parameters))
+        };
+        let mut stack = vec![(0, 0)];
+        while let Some((target, source)) = stack.pop() {
+            let target_node = tree.node(target);
+            let source_node = tree.node(source);
+            if (target_node.is_leaf() && target_node.items.is_empty())
+                || (source_node.is_leaf() && source_node.items.is_empty())
+            {
+                continue;
+            }
+
+            let target_center = target_node.boundary.center();
+            let source_center = source_node.boundary.center();
+            let radii = (target_node.boundary.max - target_node.boundary.min).length() / 2.
+                + (source_node.boundary.max - source_node.boundary.min).length() / 2.;
+            let distance = target_center.distance(source_center);
+            // every pair between the two nodes also has to be further apart
+            // than epsilon, closer ones only get skipped when summed directly
+            let separated = radii < self.opening * distance && distance - radii >= minimum_gap;
+            if target != source && separated {
+                let separation = Complex::from_vec2(target_center) - Complex::from_vec2(source_center);
+                expansion.fill_derivatives(separation, &mut derivatives);
+                let multipole = &self.multipoles[(source * size)..((source + 1) * size)];
+                let local = &mut self.locals[(target * size)..((target + 1) * size)];
+                expansion.multipole_to_local(multipole, &derivatives, local);
+                continue;
+            }
+
+            match (target_node.children, source_node.children) {
+                (None, None) => target_node.items.iter().for_each(|&target_index| {
+                    source_node.items.iter().for_each(|&source_index| {
+                        accelerations[target_index] += direct(target_index, source_index);
+                    });
+                }),
+                (Some(target_children), None) => stack.extend(target_children.map(|child| (child, source))),
+                (None, Some(source_children)) => stack.extend(source_children.map(|child| (target, child))),
+                (Some(target_children), Some(source_children)) => {
+                    if target == source {
+                        target_children.for_each(|target_child| {
+                            stack.extend(
+                                source_children.clone().map(|source_child| (target_child, source_child)),
+                            );
+                        });
+                    }
+                    else if tree.node_size(target) >= tree.node_size(source) {
+                        stack.extend(target_children.map(|child| (child, source)));
+                    }
+                    else {
+                        stack.extend(source_children.map(|child| (target, child)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// local to local down into every child, then local to particle in the
+    /// leaves
+    fn downward_pass<Tree, P>(
+        &mut self, expansion: &Expansion, tree: &Tree, particles: &[P], parameters: ForceParameters,
+        accelerations: &mut [Complex],
+    ) where
+        Tree: TreeHierarchy<Boundary = BoundingBox>,
+        P: MassPoint<Vector = Vec2>,
+    {
+        let size = expansion.terms.len();
+        let mut powers = expansion.powers();
+        let mut parent_coefficients = vec![Complex::ZERO; size];
+        // parents always come out before their children
+        tree.pre_order().for_each(|node| {
+            let center = Complex::from_vec2(node.boundary.center());
+            parent_coefficients.copy_from_slice(&self.locals[(node.index * size)..((node.index + 1) * size)]);
+            let Some(children) = node.children
+            else {
+                node.items.iter().for_each(|&item_index| {
+                    let offset = Complex::from_vec2(particles[item_index].mass_position()) - center;
+                    expansion.fill_powers(offset, &mut powers);
+                    // the gradient of a real potential is 2 d/dconj(z)
+                    let mut gradient = Complex::ZERO;
+                    expansion.terms.iter().zip(&parent_coefficients).for_each(|(&(k, l), &coefficient)| {
+                        if l > 0 {
+                            gradient += coefficient * powers.z[k] * powers.z_conj[l - 1] * (2. * l as f64);
+                        }
+                    });
+                    accelerations[item_index] += gradient * parameters.gravity as f64;
+                });
+                return;
+            };
+
+            children.for_each(|child| {
+                let shift = Complex::from_vec2(tree.node_boundary(child).center()) - center;
+                expansion.fill_powers(shift, &mut powers);
+                let coefficients = &mut self.locals[(child * size)..((child + 1) * size)];
+                expansion.terms.iter().zip(coefficients.iter_mut()).for_each(|(&(a, b), coefficient)| {
+                    (a..=expansion.order).for_each(|k| {
+                        (b..=(expansion.order - k)).for_each(|l| {
+                            *coefficient += parent_coefficients[expansion.term_index(k, l)]
+                                * powers.z[k - a]
+                                * powers.z_conj[l - b]
+                                * (expansion.binomial(k, a) * expansion.binomial(l, b));
+                        });
+                    });
+                });
+            });
+        });
+    }
+}
+
+/// the tables every expansion of one order shares
+struct Expansion {
+    order: usize,
+    // every (k, l) with k + l <= order, in the order each node stores them
+    terms: Vec<(usize, usize)>,
+    // 1 / n! up to 2 * order
+    inverse_factorials: Vec<f64>,
+    // d^n/dz^n z^-1/2 = half_power_derivatives[n] * z^(-1/2 - n)
+    half_power_derivatives: Vec<f64>,
+    // for every target term (a, b) with a <= b, the spot in the derivative
+    // table of (k + a, l + b) for every source term (k, l)
+    local_derivatives: Vec<Vec<usize>>,
+    // where the conjugate (b, a) of each term sits
+    mirrored_terms: Vec<usize>,
+}
+
+/// z^n and conj(z)^n for every n an expansion needs
+struct Powers {
+    z: Vec<Complex>,
+    z_conj: Vec<Complex>,
+}
+
+impl Expansion {
+    fn build(order: usize) -> Self {
+        let table_length = 2 * order + 1;
+        let mut inverse_factorials = vec![1.; table_length];
+        let mut half_power_derivatives = vec![1.; table_length];
+        (1..table_length).for_each(|n| {
+            inverse_factorials[n] = inverse_factorials[n - 1] / n as f64;
+            half_power_derivatives[n] = half_power_derivatives[n - 1] * (-0.5 - (n - 1) as f64);
+        });
+
+        let terms: Vec<(usize, usize)> =
+            (0..=order).flat_map(|k| (0..=(order - k)).map(move |l| (k, l))).collect();
+        let mut expansion = Expansion {
+            order,
+            terms,
+            inverse_factorials,
+            half_power_derivatives,
+            local_derivatives: Vec::new(),
+            mirrored_terms: Vec::new(),
+        };
+        expansion.mirrored_terms = expansion.terms.iter().map(|&(k, l)| expansion.term_index(l, k)).collect();
+        expansion.local_derivatives = expansion
+            .terms
+            .iter()
+            .map(|&(a, b)| expansion.terms.iter().map(|&(k, l)| (k + a) * table_length + (l + b)).collect())
+            .collect();
+
+        expansion
+    }
+
+    /// terms are laid out k major, each k followed by l = 0..=(order - k)
+    fn term_index(&self, k: usize, l: usize) -> usize {
+        k * (self.order + 1) - k * k.saturating_sub(1) / 2 + l
+    }
+
+    fn binomial(&self, n: usize, k: usize) -> f64 {
+        (1. / self.inverse_factorials[n]) * self.inverse_factorials[k] * self.inverse_factorials[n - k]
+    }
+
+    fn powers(&self) -> Powers {
+        Powers { z: vec![Complex::ONE; self.order + 1], z_conj: vec![Complex::ONE; self.order + 1] }
+    }
+
+    fn fill_powers(&self, z: Complex, powers: &mut Powers) {
+        (1..=self.order).for_each(|n| {
+            powers.z[n] = powers.z[n - 1] * z;
+            powers.z_conj[n] = powers.z_conj[n - 1] * z.conj();
+        });
+    }
+
+    fn derivatives(&self) -> Vec<Complex> {
+        let table_length = 2 * self.order + 1;
+        vec![Complex::ZERO; table_length * table_length]
+    }
+
+    /// d^n/dz^n d^m/dconj(z)^m of 1/|z| for n + m <= 2 * order, which is
+    /// h_n h_m z^-n conj(z)^-m / |z| since 1/|z| = z^-1/2 conj(z)^-1/2
+    fn fill_derivatives(&self, z: Complex, derivatives: &mut [Complex]) {
+        let table_length = 2 * self.order + 1;
+        let inverse = z.recip();
+        let inverse_length = 1. / z.norm_squared().sqrt();
+        let mut inverse_power = Complex::ONE;
+        (0..table_length).for_each(|n| {
+            // each row starts at m = 0, the rest of it steps along conj(z)
+            let scale = self.half_power_derivatives[n] * inverse_length;
+            derivatives[n * table_length] = inverse_power * scale;
+            inverse_power = inverse_power * inverse;
+        });
+        (0..table_length).for_each(|n| {
+            (1..(table_length - n)).for_each(|m| {
+                derivatives[n * table_length + m] = derivatives[n * table_length + m - 1]
+                    * inverse.conj()
+                    * (self.half_power_derivatives[m] / self.half_power_derivatives[m - 1]);
+            });
+        });
+    }
+
+    /// adds the source multipole's field as a taylor series around the target
+    /// center. `derivatives` comes from `fill_derivatives` with the target
+    /// center minus the source center. the potential is real, so only half the
+    /// terms get summed and the rest are their conjugates
+    fn multipole_to_local(&self, multipole: &[Complex], derivatives: &[Complex], local: &mut [Complex]) {
+        self.terms.iter().enumerate().for_each(|(target_term, &(a, b))| {
+            if a > b {
+                return;
+            }
+
+            let mut sum = Complex::ZERO;
+            multipole.iter().zip(&self.local_derivatives[target_term]).for_each(
+                |(&coefficient, &derivative)| {
+                    sum += coefficient * derivatives[derivative];
+                },
+            );
+            let sum = sum * (self.inverse_factorials[a] * self.inverse_factorials[b]);
+            local[target_term] += sum;
+            if a != b {
+                local[self.mirrored_terms[target_term]] += sum.conj();
+            }
+        });
+    }
+}
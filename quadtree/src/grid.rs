@@ -0,0 +1,347 @@
+use std::collections::BinaryHeap;
+
+use glam::IVec2;
+use glam::Vec2;
+
+use crate::quadtree::DistanceEntry;
+use crate::quadtree::PositionPlanar;
+use crate::spatial_index::SpatialIndex;
+use crate::utils::BoundingBox;
+
+/// fixed grid of equally sized cells over `bounds`. cheaper than a tree when
+/// the items are spread out evenly. items outside of `bounds` are filed under
+/// the closest edge cell, so nothing gets lost, it just gets slower
+#[repr(C)]
+#[derive(Debug)]
+pub struct UniformGrid {
+    pub bounds: BoundingBox,
+    pub cell_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+    // cell c holds item_indices[cell_starts[c]..cell_starts[c + 1]], row major
+    pub cell_starts: Vec<usize>,
+    pub item_indices: Vec<usize>,
+}
+
+impl UniformGrid {
+    pub fn build(cell_size: f32, bounds: BoundingBox) -> Self {
+        let columns = ((bounds.width() / cell_size).ceil() as usize).max(1);
+        let rows = ((bounds.height() / cell_size).ceil() as usize).max(1);
+        UniformGrid {
+            bounds,
+            cell_size,
+            columns,
+            rows,
+            cell_starts: vec![0; columns * rows + 1],
+            item_indices: Vec::new(),
+        }
+    }
+
+    /// lays a new grid with the same cell size over `bounds`, empty until the
+    /// next `construct_grid`
+    pub fn set_bounds(&mut self, bounds: BoundingBox) {
+        *self = UniformGrid::build(self.cell_size, bounds);
+    }
+
+    /// column and row of the cell the point belongs in, clamped to the grid
+    pub fn cell_of(&self, point: Vec2) -> (usize, usize) {
+        let offset = ((point - self.bounds.min) / self.cell_size).floor();
+        let column = offset.x.clamp(0., (self.columns - 1) as f32) as usize;
+        let row = offset.y.clamp(0., (self.rows - 1) as f32) as usize;
+        (column, row)
+    }
+
+    pub fn cell_items(&self, column: usize, row: usize) -> &[usize] {
+        let cell = row * self.columns + column;
+        &self.item_indices[self.cell_starts[cell]..self.cell_starts[cell + 1]]
+    }
+
+    /// counting sort of the items by cell
+    pub fn construct_grid<T>(&mut self, items: &[T])
+    where
+        T: PositionPlanar,
+    {
+        let cells: Vec<usize> = items
+            .iter()
+            .map(|item| {
+                let (column, row) = self.cell_of(item.position());
+                row * self.columns + column
+            })
+            .collect();
+
+        self.cell_starts.iter_mut().for_each(|start| *start = 0);
+        cells.iter().for_each(|&cell| self.cell_starts[cell + 1] += 1);
+        (1..self.cell_starts.len()).for_each(|cell| self.cell_starts[cell] += self.cell_starts[cell - 1]);
+
+        let mut next = self.cell_starts.clone();
+        self.item_indices.clear();
+        self.item_indices.resize(items.len(), 0);
+        cells.iter().enumerate().for_each(|(item_index, &cell)| {
+            self.item_indices[next[cell]] = item_index;
+            next[cell] += 1;
+        });
+    }
+
+    pub fn query_range<T>(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        CellGrid::query_range(self, boundary, items)
+    }
+
+    pub fn query_radius<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        CellGrid::query_radius(self, center, radius, items)
+    }
+
+    pub fn nearest<T>(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        CellGrid::nearest(self, point, count, items)
+    }
+}
+
+impl CellGrid for UniformGrid {
+    fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell_coordinates(&self, point: Vec2) -> IVec2 {
+        let (column, row) = self.cell_of(point);
+        IVec2::new(column as i32, row as i32)
+    }
+
+    fn occupied_cells(&self) -> Option<(IVec2, IVec2)> {
+        Some((IVec2::ZERO, IVec2::new(self.columns as i32 - 1, self.rows as i32 - 1)))
+    }
+
+    fn items_at(&self, cell: IVec2) -> &[usize] {
+        self.cell_items(cell.x as usize, cell.y as usize)
+    }
+
+    fn stored_cell_count(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn for_each_stored_cell<F>(&self, mut callback: F)
+    where
+        F: FnMut(IVec2, &[usize]),
+    {
+        (0..self.rows).for_each(|row| {
+            (0..self.columns).for_each(|column| {
+                callback(IVec2::new(column as i32, row as i32), self.cell_items(column, row));
+            });
+        });
+    }
+}
+
+/// a grid of `cell_size` squares addressed by (column, row). `UniformGrid`
+/// stores every cell and `SpatialHash` only the occupied ones, the queries
+/// are the same for both and only ever look at cells that are stored
+pub(crate) trait CellGrid {
+    fn cell_size(&self) -> f32;
+
+    /// the cell an item at `point` is filed under
+    fn cell_coordinates(&self, point: Vec2) -> IVec2;
+
+    /// smallest and largest cell that can hold anything, `None` if none can
+    fn occupied_cells(&self) -> Option<(IVec2, IVec2)>;
+
+    fn items_at(&self, cell: IVec2) -> &[usize];
+
+    fn stored_cell_count(&self) -> usize;
+
+    fn for_each_stored_cell<F>(&self, callback: F)
+    where
+        F: FnMut(IVec2, &[usize]);
+
+    fn query_range<T>(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        let mut output = Vec::new();
+        self.for_each_cell_between(boundary.min, boundary.max, |cell_items| {
+            output.extend(
+                cell_items.iter().filter(|&&item_index| boundary.contains(items[item_index].position())),
+            );
+        });
+        output
+    }
+
+    fn query_radius<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        let radius_squared = radius * radius;
+        let mut output = Vec::new();
+        self.for_each_cell_between(
+            center - Vec2::splat(radius),
+            center + Vec2::splat(radius),
+            |cell_items| {
+                output.extend(cell_items.iter().filter(|&&item_index| {
+                    items[item_index].position().distance_squared(center) <= radius_squared
+                }));
+            },
+        );
+        output
+    }
+
+    /// searches rings of cells around the point's cell until the next ring
+    /// can't hold anything closer than the `count` best found so far, or
+    /// there are no occupied cells left outside of it. once the rings would
+    /// cover more cells than are stored (a far away outlier stretches them
+    /// out) the remaining stored cells are checked directly instead
+    fn nearest<T>(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        let Some((occupied_min, occupied_max)) = self.occupied_cells()
+        else {
+            return Vec::new();
+        };
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let center = self.cell_coordinates(point);
+        let last_ring = (center.as_i64vec2() - occupied_min.as_i64vec2())
+            .abs()
+            .max((occupied_max.as_i64vec2() - center.as_i64vec2()).abs())
+            .max_element();
+        // everything `ring` cells out is at least (ring - 1) cells away
+        let ring_distance_squared = |ring: i64| ((ring - 1).max(0) as f32 * self.cell_size()).powi(2);
+        let mut best: BinaryHeap<DistanceEntry> = BinaryHeap::with_capacity(count + 1);
+        let consider = |best: &mut BinaryHeap<DistanceEntry>, cell: IVec2| {
+            self.items_at(cell).iter().for_each(|&item_index| {
+                let distance_squared = items[item_index].position().distance_squared(point);
+                best.push(DistanceEntry::build(distance_squared, item_index));
+                if best.len() > count {
+                    best.pop();
+                }
+            });
+        };
+        let is_done = |best: &BinaryHeap<DistanceEntry>, ring: i64| {
+            best.len() == count
+                && best.peek().is_some_and(|worst| ring_distance_squared(ring) > worst.distance_squared)
+        };
+
+        let mut visited = 0;
+        for ring in 0..=last_ring {
+            if is_done(&best, ring) {
+                break;
+            }
+
+            let ring_cells = (8 * ring).max(1);
+            if visited + ring_cells > self.stored_cell_count() as i64 {
+                let mut remaining = Vec::new();
+                self.for_each_stored_cell(|cell, _| {
+                    let cell_ring = (cell.as_i64vec2() - center.as_i64vec2()).abs().max_element();
+                    if cell_ring >= ring {
+                        remaining.push((cell_ring, cell.y, cell.x));
+                    }
+                });
+                remaining.sort_unstable();
+                for (cell_ring, y, x) in remaining {
+                    if is_done(&best, cell_ring) {
+                        break;
+                    }
+                    consider(&mut best, IVec2::new(x, y));
+                }
+                break;
+            }
+            visited += ring_cells;
+
+            for_each_ring_cell(center, ring as i32, occupied_min, occupied_max, |cell| {
+                consider(&mut best, cell)
+            });
+        }
+
+        best.into_sorted_vec().into_iter().map(|entry| (entry.index, entry.distance_squared.sqrt())).collect()
+    }
+
+    /// calls back with every stored cell overlapping the box from `min` to
+    /// `max`. walks the stored cells instead of the coordinates when the box
+    /// covers more cells than there are
+    fn for_each_cell_between<F>(&self, min: Vec2, max: Vec2, mut callback: F)
+    where
+        F: FnMut(&[usize]),
+    {
+        let Some((occupied_min, occupied_max)) = self.occupied_cells()
+        else {
+            return;
+        };
+        let min = self.cell_coordinates(min).max(occupied_min);
+        let max = self.cell_coordinates(max).min(occupied_max);
+        if min.x > max.x || min.y > max.y {
+            return;
+        }
+
+        let span = (max.as_i64vec2() - min.as_i64vec2()) + 1;
+        if span.x * span.y > self.stored_cell_count() as i64 {
+            self.for_each_stored_cell(|cell, cell_items| {
+                if cell.cmpge(min).all() && cell.cmple(max).all() {
+                    callback(cell_items);
+                }
+            });
+            return;
+        }
+
+        (min.y..=max.y).for_each(|y| {
+            (min.x..=max.x).for_each(|x| callback(self.items_at(IVec2::new(x, y))));
+        });
+    }
+}
+
+/// the cells exactly `ring` steps (chebyshev) away from `center` that are
+/// inside `min`..=`max`
+fn for_each_ring_cell<F>(center: IVec2, ring: i32, min: IVec2, max: IVec2, mut callback: F)
+where
+    F: FnMut(IVec2),
+{
+    let inside = |cell: IVec2| cell.cmpge(min).all() && cell.cmple(max).all();
+    if ring == 0 {
+        if inside(center) {
+            callback(center);
+        }
+        return;
+    }
+
+    let low = center.saturating_sub(IVec2::splat(ring));
+    let high = center.saturating_add(IVec2::splat(ring));
+    // the top and bottom rows, corners included
+    [low.y, high.y].into_iter().filter(|y| (min.y..=max.y).contains(y)).for_each(|y| {
+        (low.x.max(min.x)..=high.x.min(max.x)).for_each(|x| callback(IVec2::new(x, y)));
+    });
+    // the left and right columns in between
+    [low.x, high.x].into_iter().filter(|x| (min.x..=max.x).contains(x)).for_each(|x| {
+        ((low.y + 1).max(min.y)..=(high.y - 1).min(max.y)).for_each(|y| callback(IVec2::new(x, y)));
+    });
+}
+
+impl<T> SpatialIndex<T> for UniformGrid
+where
+    T: PositionPlanar,
+{
+    fn build(&mut self, items: &[T]) {
+        self.construct_grid(items);
+    }
+
+    fn set_bounds(&mut self, bounds: BoundingBox) {
+        UniformGrid::set_bounds(self, bounds);
+    }
+
+    fn query_box(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize> {
+        self.query_range(boundary, items)
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize> {
+        UniformGrid::query_radius(self, center, radius, items)
+    }
+
+    fn nearest(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)> {
+        UniformGrid::nearest(self, point, count, items)
+    }
+}
@@ -0,0 +1,66 @@
+pub mod aggregate;
+pub mod barnes_hut;
+pub mod fmm;
+pub mod grid;
+pub mod loose;
+pub mod morton;
+pub mod octree;
+pub mod pairs;
+pub mod parallel;
+pub mod quadtree;
+pub mod raycast;
+pub mod spatial_hash;
+pub mod spatial_index;
+pub mod state;
+pub mod stats;
+pub mod traversal;
+pub mod utils;
+pub mod validate;
+
+pub use aggregate::ContentBounds;
+pub use aggregate::ItemCount;
+pub use aggregate::NodeAggregate;
+pub use aggregate::TreeHierarchy;
+pub use barnes_hut::BarnesHutNode;
+pub use barnes_hut::BarnesHutWrapper;
+pub use barnes_hut::ForceParameters;
+pub use barnes_hut::MassPoint;
+pub use barnes_hut::MassVector;
+pub use barnes_hut::QuadrupoleNode;
+pub use fmm::FmmSolver;
+pub use grid::UniformGrid;
+pub use loose::BoundedPlanar;
+pub use loose::LooseQuadTree;
+pub use octree::Octree;
+pub use octree::OctreeNode;
+pub use octree::PositionSpatial;
+pub use quadtree::DuplicatePolicy;
+pub use quadtree::OutOfBoundsError;
+pub use quadtree::OutOfBoundsPolicy;
+pub use quadtree::PositionPlanar;
+pub use quadtree::QuadTree;
+pub use quadtree::QuadTreeItems;
+pub use quadtree::QuadTreeNode;
+pub use quadtree::QuadTreeOwner;
+pub use quadtree::SplitPolicy;
+pub use raycast::ItemExtent;
+pub use raycast::RadiusPlanar;
+pub use raycast::RayCaster;
+pub use raycast::RayHit;
+pub use spatial_hash::SpatialHash;
+pub use spatial_index::BruteForce;
+pub use spatial_index::IndexedPoint;
+pub use spatial_index::SpatialIndex;
+pub use state::ForceSolver;
+pub use state::Particle;
+pub use state::SimulationConfig;
+pub use state::State;
+pub use stats::TreeStats;
+pub use traversal::NodeRef;
+pub use traversal::TreeTraversal;
+pub use traversal::Visit;
+pub use traversal::Visitor;
+pub use utils::BoundingBox;
+pub use utils::BoundingBox3;
+pub use validate::TreeViolation;
+pub use validate::ValidationError;
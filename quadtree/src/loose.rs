@@ -0,0 +1,202 @@
+use glam::Vec2;
+
+use crate::quadtree::QuadTree;
+use crate::quadtree::QuadTreeNode;
+use crate::raycast::RadiusPlanar;
+use crate::utils::BoundingBox;
+
+/// anything that takes up space instead of just sitting on a point
+pub trait BoundedPlanar {
+    fn bounds(&self) -> BoundingBox;
+}
+
+/// quadtree for items with an extent. the node layout comes from a regular
+/// `QuadTree` over the item centers, then every item is stored in the deepest
+/// node whose *loose* boundary (the real one grown by `looseness`) still holds
+/// its whole extent. stems can hold items too, so an item never gets split
+/// across nodes and small items straddling a split line don't pile up in the
+/// root
+#[repr(C)]
+#[derive(Debug)]
+pub struct LooseQuadTree {
+    // same layout as QuadTree->nodes, but data_head/data_len point at the items
+    // living in that node (stem or leaf) inside item_indices
+    pub nodes: Vec<QuadTreeNode>,
+    // each node's boundary grown by looseness, lines up with nodes
+    pub loose_bounds: Vec<BoundingBox>,
+    pub item_indices: Vec<usize>,
+    // items whose center is outside of the root, checked by every query
+    pub overflow: Vec<usize>,
+    // 1 is a plain quadtree, 2 makes every loose boundary twice as wide
+    pub looseness: f32,
+    // point tree over the item centers which decides where nodes split
+    pub structure: QuadTree,
+    centers: Vec<Vec2>,
+}
+
+impl LooseQuadTree {
+    pub const DEFAULT_LOOSENESS: f32 = 2.;
+
+    pub fn build(leaf_capacity: usize, boundary: BoundingBox) -> Self {
+        LooseQuadTree {
+            nodes: Vec::new(),
+            loose_bounds: Vec::new(),
+            item_indices: Vec::new(),
+            overflow: Vec::new(),
+            looseness: Self::DEFAULT_LOOSENESS,
+            structure: QuadTree::build(leaf_capacity, boundary),
+            centers: Vec::new(),
+        }
+    }
+
+    pub fn with_looseness(mut self, looseness: f32) -> Self {
+        self.looseness = looseness.max(1.);
+        self
+    }
+
+    pub fn construct_tree<T>(&mut self, items: &[T])
+    where
+        T: BoundedPlanar,
+    {
+        self.centers.clear();
+        self.centers.extend(items.iter().map(|item| item.bounds().center()));
+        self.structure.construct_tree(&self.centers);
+
+        self.nodes.clear();
+        self.nodes.extend(self.structure.nodes.iter().map(|node| QuadTreeNode {
+            data_head: None,
+            data_len: 0,
+            ..*node
+        }));
+        self.loose_bounds.clear();
+        let looseness = self.looseness;
+        self.loose_bounds.extend(self.nodes.iter().map(|node| Self::loosen(&node.boundary, looseness)));
+        self.overflow.clear();
+
+        // find every item's home node, then counting sort the items by home
+        let homes: Vec<Option<usize>> = items.iter().map(|item| self.home_node(&item.bounds())).collect();
+        homes.iter().flatten().for_each(|&home| {
+            self.nodes[home].data_len += 1;
+        });
+        let mut data_head = 0;
+        self.nodes.iter_mut().filter(|node| node.data_len > 0).for_each(|node| {
+            node.data_head = Some(data_head);
+            data_head += node.data_len;
+        });
+
+        self.item_indices.clear();
+        self.item_indices.resize(data_head, 0);
+        let mut cursors: Vec<usize> = self.nodes.iter().map(|node| node.data_head.unwrap_or(0)).collect();
+        homes.iter().enumerate().for_each(|(item_index, home)| match home {
+            Some(home) => {
+                self.item_indices[cursors[*home]] = item_index;
+                cursors[*home] += 1;
+            }
+            None => self.overflow.push(item_index),
+        });
+    }
+
+    /// items whose extent overlaps the boundary
+    pub fn query_range<T>(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize>
+    where
+        T: BoundedPlanar,
+    {
+        let test = |bounds: &BoundingBox| bounds.overlaps(boundary);
+        self.search(test, |item_index| test(&items[item_index].bounds()))
+    }
+
+    /// items whose extent touches the circle. the extent is the item's
+    /// bounding box, so round items near its corners come back too even
+    /// though they miss the circle, `query_circle_round` leaves those out
+    pub fn query_circle<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: BoundedPlanar,
+    {
+        let radius_squared = radius * radius;
+        let test = |bounds: &BoundingBox| bounds.distance_squared(center) <= radius_squared;
+        self.search(test, |item_index| test(&items[item_index].bounds()))
+    }
+
+    /// items whose own circle touches the circle, for round items
+    pub fn query_circle_round<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: BoundedPlanar + RadiusPlanar,
+    {
+        let radius_squared = radius * radius;
+        self.search(
+            |bounds| bounds.distance_squared(center) <= radius_squared,
+            |item_index| {
+                let reach = radius + items[item_index].radius();
+                items[item_index].position().distance_squared(center) <= reach * reach
+            },
+        )
+    }
+
+    /// the items stored directly in a node, stems included
+    pub fn node_items(&self, node_index: usize) -> &[usize] {
+        let node = &self.nodes[node_index];
+        match node.data_head {
+            Some(data_head) => &self.item_indices[data_head..(data_head + node.data_len)],
+            None => &[],
+        }
+    }
+
+    fn loosen(boundary: &BoundingBox, looseness: f32) -> BoundingBox {
+        let grow = Vec2::new(boundary.width(), boundary.height()) * (looseness - 1.) / 2.;
+        BoundingBox::build(boundary.min - grow, boundary.max + grow)
+    }
+
+    /// deepest node that holds the item's center and whose loose boundary
+    /// holds its whole extent. the root takes anything centered inside it
+    fn home_node(&self, bounds: &BoundingBox) -> Option<usize> {
+        let center = bounds.center();
+        let mut target_node_index = QuadTree::ROOT_INDEX;
+        if !self.nodes.get(target_node_index)?.boundary.contains(center) {
+            return None;
+        }
+
+        while let Some(leaf_start) = self.nodes[target_node_index].leaves {
+            // the children can meet anywhere depending on the split policy
+            let first = self.nodes[leaf_start].boundary;
+            let leaf = leaf_start + BoundingBox::quadrant_around(Vec2::new(first.min.x, first.max.y), center);
+            if !self.loose_bounds[leaf].contains_box(bounds) {
+                break;
+            }
+            target_node_index = leaf;
+        }
+
+        Some(target_node_index)
+    }
+
+    /// walks every node whose loose boundary passes `node_test` and keeps the
+    /// items that pass `item_test`. the root is always opened since items
+    /// centered in it can stick out past its loose boundary
+    fn search<N, I>(&self, node_test: N, item_test: I) -> Vec<usize>
+    where
+        N: Fn(&BoundingBox) -> bool,
+        I: Fn(usize) -> bool,
+    {
+        let mut output = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(QuadTree::ROOT_INDEX);
+        }
+
+        while let Some(target_node_index) = stack.pop() {
+            if target_node_index != QuadTree::ROOT_INDEX && !node_test(&self.loose_bounds[target_node_index])
+            {
+                continue;
+            }
+
+            output.extend(
+                self.node_items(target_node_index).iter().filter(|&&item_index| item_test(item_index)),
+            );
+            if let Some(leaf_start) = self.nodes[target_node_index].leaves {
+                stack.extend(leaf_start..(leaf_start + QuadTree::STEM_LEAF_COUNT));
+            }
+        }
+        output.extend(self.overflow.iter().filter(|&&item_index| item_test(item_index)));
+
+        output
+    }
+}
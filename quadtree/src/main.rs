@@ -1,9 +1,5 @@
-mod barnes_hut;
 mod compiled_shaders;
-mod quadtree;
 mod renderer;
-mod state;
-mod utils;
 
 use std::collections::HashMap;
 use std::ffi::c_void;
@@ -16,9 +12,10 @@ use sokol::glue as sgl;
 use sokol::log as slog;
 use sokol::time;
 
+use quadtree::state::State;
+use quadtree::utils::mouse_to_screen;
+
 use renderer::PrimitiveRenderer;
-use state::State;
-use utils::Clock;
 
 extern "C" fn init(ptr: *mut c_void) {
     let state = unsafe { &mut *(ptr as *mut ApplicationState) };
@@ -73,11 +70,16 @@ struct ApplicationState {
     renderer: PrimitiveRenderer,
     state: State,
     clock: Clock,
+    // print QuadTree::stats to stderr every time the barnes-hut tree is built
+    log_tree_stats: bool,
 }
 
 impl ApplicationState {
     fn update(&mut self) {
         self.state.update_barnes_hut(self.clock.frame_time);
+        if self.log_tree_stats {
+            eprintln!("{}", self.state.quadtree.stats());
+        }
     }
 
     fn handle_event(&mut self, event: sapp::Event) {
@@ -87,11 +89,48 @@ impl ApplicationState {
         if event._type == sapp::EventType::Resized {
             self.state.update_dimensions(sapp::widthf(), sapp::heightf());
         }
-        self.state.handle_event(event);
+        if event.mouse_button == sapp::Mousebutton::Left && event._type == sapp::EventType::MouseDown {
+            let position = mouse_to_screen(event.mouse_x, event.mouse_y, &self.state.dimensions);
+            self.state.spawn_particle(position);
+        }
+        if event.mouse_button == sapp::Mousebutton::Right && event._type == sapp::EventType::MouseDown {
+            let position = mouse_to_screen(event.mouse_x, event.mouse_y, &self.state.dimensions);
+            if let Some(index) = self.state.pick_particle(position) {
+                self.state.remove_particle(index);
+            }
+        }
+        if event.key_code == sapp::Keycode::R {
+            self.state.clear_particles();
+        }
+        if event.key_code == sapp::Keycode::S && event._type == sapp::EventType::KeyDown {
+            self.log_tree_stats = !self.log_tree_stats;
+        }
+        // dumps the current tree for bug reports, pipe it into `dot -Tsvg`
+        if event.key_code == sapp::Keycode::D && event._type == sapp::EventType::KeyDown {
+            println!("{}", self.state.quadtree.to_dot());
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Clock {
+    curr_time: u64,
+    last_time: u64,
+    frame_time: f32,
+}
+
+impl Clock {
+    fn update(&mut self, now: u64) {
+        self.last_time = self.curr_time;
+        self.curr_time = now;
+        self.frame_time = time::sec(self.curr_time - self.last_time) as f32;
     }
 }
 
 fn main() {
+    let mut simulation = State::build(1920, 1080);
+    simulation.config.threads = std::thread::available_parallelism().map_or(1, |count| count.get());
+
     let state = ApplicationState {
         renderer: PrimitiveRenderer {
             render_targets: HashMap::new(),
@@ -99,8 +138,9 @@ fn main() {
             set_pipeline: gfx::Pipeline::new(),
             set_pass_action: gfx::PassAction::new(),
         },
-        state: State::build(1920, 1080),
+        state: simulation,
         clock: Clock { curr_time: 0, last_time: 0, frame_time: 0. },
+        log_tree_stats: false,
     };
 
     let state_ptr = Box::into_raw(Box::from(state)) as *mut c_void;
@@ -0,0 +1,212 @@
+use glam::Vec2;
+
+use crate::quadtree::OutOfBoundsError;
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+use crate::quadtree::SplitPolicy;
+use crate::utils::BoundingBox;
+
+/// morton digits are (y bit, x bit) but the leaves are stored in unit circle
+/// quadrant order, so this maps a digit to the matching leaf offset
+const DIGIT_TO_QUADRANT: [usize; 4] = [1, 0, 2, 3];
+/// two bits per level have to fit into a u64
+const MAX_CODE_LEVELS: usize = 31;
+
+impl QuadTree {
+    /// bulk builder. every item gets a morton (z-curve) code relative to the
+    /// root boundary, the codes are radix sorted and the nodes are then emitted
+    /// straight from the sorted runs. the finished tree answers queries exactly
+    /// like one from `construct_tree`, but the leaves are laid out in z-order
+    pub fn construct_tree_morton<T>(&mut self, items: &[T])
+    where
+        T: PositionPlanar,
+    {
+        let _ = self.try_construct_tree_morton(items);
+    }
+
+    pub fn try_construct_tree_morton<T>(&mut self, items: &[T]) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar,
+    {
+        // morton codes only describe center splits
+        if self.split_policy != SplitPolicy::Center {
+            return self.try_construct_tree(items);
+        }
+        self.weights.clear();
+        let rejected = self.gather_items(items);
+
+        // codes only cover the levels a balanced tree would need, anything
+        // deeper (clusters) gets finished off by the regular partition
+        let stored_count = self.item_indices.len();
+        let balanced_levels = (stored_count / self.leaf_capacity.max(1)).max(1).ilog(4) as usize + 1;
+        let levels = self.max_depth.min(MAX_CODE_LEVELS).min(balanced_levels);
+        let root_boundary = self.nodes[Self::ROOT_INDEX].boundary;
+        let mut coded: Vec<(u64, usize)> = self
+            .item_indices
+            .iter()
+            .map(|&item_index| {
+                (morton_code(&root_boundary, items[item_index].position(), levels), item_index)
+            })
+            .collect();
+        radix_sort(&mut coded, levels * 2);
+        self.item_indices.iter_mut().zip(&coded).for_each(|(stored, &(_, item_index))| {
+            *stored = item_index;
+        });
+
+        let mut scratch = vec![0; stored_count];
+        self.emit_recursive(Self::ROOT_INDEX, &coded, levels, items, &mut scratch);
+
+        // ensure tree is constructed in a logical manner
+        {
+            debug_assert!(self.nodes.len() % 4 == 1); // must add one for the root
+        }
+
+        if rejected.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(OutOfBoundsError { rejected })
+        }
+    }
+
+    /// permutes `items` into the order the tree stores them in (z-order after
+    /// `construct_tree_morton`) and renumbers the tree to match, so walking a
+    /// leaf touches neighboring memory. items that aren't in the tree at all
+    /// end up at the back
+    pub fn reorder_items<T>(&mut self, items: &mut [T]) {
+        if self.stale_indices > 0 {
+            self.compact();
+        }
+
+        let mut new_order = Vec::with_capacity(items.len());
+        new_order.extend_from_slice(&self.item_indices);
+        new_order.extend_from_slice(&self.overflow);
+        let mut stored = vec![false; items.len()];
+        new_order.iter().for_each(|&item_index| {
+            stored[item_index] = true;
+        });
+        new_order.extend((0..items.len()).filter(|&item_index| !stored[item_index]));
+        // the weights move along with their items
+        if !self.weights.is_empty() {
+            self.weights = new_order
+                .iter()
+                .map(|&item_index| self.weights.get(item_index).copied().unwrap_or(1.))
+                .collect();
+        }
+
+        // follows each cycle of the permutation, swapping as it goes
+        let mut visited = vec![false; items.len()];
+        (0..items.len()).for_each(|start| {
+            let mut current = start;
+            while !visited[current] {
+                visited[current] = true;
+                let next = new_order[current];
+                if next == start {
+                    break;
+                }
+                items.swap(current, next);
+                current = next;
+            }
+        });
+
+        let stored_count = self.item_indices.len();
+        self.item_indices.iter_mut().enumerate().for_each(|(new_index, stored)| {
+            *stored = new_index;
+        });
+        self.overflow.iter_mut().enumerate().for_each(|(offset, stored)| {
+            *stored = stored_count + offset;
+        });
+    }
+
+    fn emit_recursive<T>(
+        &mut self, target_node_index: usize, coded: &[(u64, usize)], levels: usize, items: &[T],
+        scratch: &mut [usize],
+    ) where
+        T: PositionPlanar,
+    {
+        let node = self.nodes[target_node_index];
+        if node.depth >= levels {
+            self.partition_recursive(target_node_index, items, &[], scratch);
+            return;
+        }
+        if node.data_len <= self.leaf_capacity || !self.can_subdivide(target_node_index, items) {
+            return;
+        }
+        let Some(data_head) = node.data_head
+        else {
+            return;
+        };
+
+        // the run is sorted, so each digit at this level is one contiguous piece
+        let shift = (levels - node.depth - 1) * 2;
+        let run = &coded[data_head..(data_head + node.data_len)];
+        let leaf_start = self.subdivide_stem_to_leaf(target_node_index, node.boundary.center());
+        self.nodes[target_node_index].data_head = None;
+        self.nodes[target_node_index].data_len = 0;
+
+        let mut piece_head = 0;
+        (0..4).for_each(|digit| {
+            let piece_len =
+                run[piece_head..].partition_point(|&(code, _)| ((code >> shift) & 0b11) as usize == digit);
+            let leaf = leaf_start + DIGIT_TO_QUADRANT[digit];
+            self.nodes[leaf].data_head = (piece_len > 0).then_some(data_head + piece_head);
+            self.nodes[leaf].data_len = piece_len;
+            piece_head += piece_len;
+        });
+
+        (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+            self.emit_recursive(leaf, coded, levels, items, scratch);
+        });
+    }
+}
+
+/// interleaved code of the point, built by halving the boundary exactly the way
+/// the tree does so the code always agrees with the node boundaries
+pub fn morton_code(boundary: &BoundingBox, point: Vec2, levels: usize) -> u64 {
+    let (mut min, mut max) = (boundary.min, boundary.max);
+    let mut code = 0;
+    (0..levels).for_each(|_| {
+        let center = (min + max) / 2.;
+        let (x_bit, y_bit) = (point.x >= center.x, point.y >= center.y);
+        code = (code << 2) | ((y_bit as u64) << 1) | x_bit as u64;
+
+        // same halves as BoundingBox::split_quadrants
+        if x_bit {
+            min.x = center.x
+        }
+        else {
+            max.x = center.x
+        }
+        if y_bit {
+            min.y = center.y
+        }
+        else {
+            max.y = center.y
+        }
+    });
+
+    code
+}
+
+/// lsd radix sort on the low `bits` of the codes, a byte at a time. stable, so
+/// equal codes keep their item order
+fn radix_sort(coded: &mut Vec<(u64, usize)>, bits: usize) {
+    let mut buffer = vec![(0, 0); coded.len()];
+    let passes = bits.div_ceil(8);
+    (0..passes).for_each(|pass| {
+        let shift = pass * 8;
+        let mut counts = [0; 257];
+        coded.iter().for_each(|&(code, _)| {
+            counts[((code >> shift) & 0xff) as usize + 1] += 1;
+        });
+        (1..counts.len()).for_each(|byte| {
+            counts[byte] += counts[byte - 1];
+        });
+        coded.iter().for_each(|&entry| {
+            let byte = ((entry.0 >> shift) & 0xff) as usize;
+            buffer[counts[byte]] = entry;
+            counts[byte] += 1;
+        });
+        std::mem::swap(coded, &mut buffer);
+    });
+}
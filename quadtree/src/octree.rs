@@ -0,0 +1,303 @@
+use std::ops::Range;
+
+use glam::Vec3;
+
+use crate::aggregate::TreeHierarchy;
+use crate::quadtree::holds_distinct_positions;
+use crate::quadtree::nearest_in_tree;
+use crate::quadtree::partition_run;
+use crate::quadtree::QuadTree;
+use crate::traversal::TreeTraversal;
+use crate::traversal::Visit;
+use crate::utils::BoundingBox3;
+
+/// 3D version of `PositionPlanar`
+pub trait PositionSpatial {
+    fn position(&self) -> Vec3;
+}
+
+impl PositionSpatial for Vec3 {
+    fn position(&self) -> Vec3 {
+        *self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct OctreeNode {
+    pub boundary: BoundingBox3,
+    // index of the first of 8 consecutive children, in `split_octants` order
+    pub leaves: Option<usize>,
+    // this leaf's run inside Octree->item_indices
+    pub data_head: Option<usize>,
+    pub data_len: usize,
+    pub depth: usize,
+}
+
+impl OctreeNode {
+    pub fn build(boundary: BoundingBox3, depth: usize) -> Self {
+        OctreeNode { boundary, leaves: None, data_head: None, data_len: 0, depth }
+    }
+}
+
+/// 3D counterpart of `QuadTree` with the same flat layout, every stem has 8
+/// children instead of 4. only does bulk construction, and items outside of
+/// the root always end up in `overflow`
+#[repr(C)]
+#[derive(Debug)]
+pub struct Octree {
+    pub nodes: Vec<OctreeNode>,
+    // every leaf owns one contiguous run of it
+    pub item_indices: Vec<usize>,
+    pub leaf_capacity: usize,
+    // leaves this deep are never split and can go over capacity
+    pub max_depth: usize,
+    // items outside of the root, checked by every query
+    pub overflow: Vec<usize>,
+}
+
+impl Octree {
+    pub const ROOT_INDEX: usize = 0;
+    pub const STEM_LEAF_COUNT: usize = 8;
+
+    pub fn build(leaf_capacity: usize, boundary: BoundingBox3) -> Self {
+        Octree {
+            nodes: vec![OctreeNode::build(boundary, 0)],
+            item_indices: Vec::new(),
+            leaf_capacity,
+            max_depth: QuadTree::DEFAULT_MAX_DEPTH,
+            overflow: Vec::new(),
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn root(&mut self) -> &mut OctreeNode {
+        &mut self.nodes[Self::ROOT_INDEX]
+    }
+
+    pub fn clear_tree(&mut self) {
+        let root = &mut self.nodes[Self::ROOT_INDEX];
+        root.leaves = None;
+        root.data_head = None;
+        root.data_len = 0;
+        self.nodes.truncate(1);
+        self.item_indices.clear();
+        self.overflow.clear();
+    }
+
+    /// same in-place counting sort as `QuadTree::construct_tree`, sharing its
+    /// `partition_run`
+    pub fn construct_tree<T>(&mut self, items: &[T])
+    where
+        T: PositionSpatial,
+    {
+        self.clear_tree();
+        let root_boundary = self.nodes[Self::ROOT_INDEX].boundary;
+        (0..items.len()).for_each(|index| {
+            if root_boundary.contains(items[index].position()) {
+                self.item_indices.push(index);
+            }
+            else {
+                self.overflow.push(index);
+            }
+        });
+
+        let stored_count = self.item_indices.len();
+        if stored_count > 0 {
+            let root = &mut self.nodes[Self::ROOT_INDEX];
+            root.data_head = Some(0);
+            root.data_len = stored_count;
+            let mut scratch = vec![0; stored_count];
+            self.partition_recursive(Self::ROOT_INDEX, items, &mut scratch);
+        }
+
+        debug_assert!(self.nodes.len() % Self::STEM_LEAF_COUNT == 1);
+    }
+
+    /// the items stored directly in a node, always empty for stems
+    pub fn leaf_items(&self, node_index: usize) -> &[usize] {
+        let node = &self.nodes[node_index];
+        match node.data_head {
+            Some(data_head) => &self.item_indices[data_head..(data_head + node.data_len)],
+            None => &[],
+        }
+    }
+
+    /// items actually inside the boundary
+    pub fn query_range<T>(&self, boundary: &BoundingBox3, items: &[T]) -> Vec<usize>
+    where
+        T: PositionSpatial,
+    {
+        let mut output = Vec::new();
+        self.search_range_node(Self::ROOT_INDEX, boundary, items, &mut output);
+        output.extend(
+            self.overflow.iter().filter(|&&item_index| boundary.contains(items[item_index].position())),
+        );
+
+        output
+    }
+
+    /// items within `radius` of `center`
+    pub fn query_radius<T>(&self, center: Vec3, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: PositionSpatial,
+    {
+        let radius_squared = radius * radius;
+        let mut output = Vec::new();
+        self.search_radius_node(Self::ROOT_INDEX, center, radius_squared, items, &mut output);
+        output.extend(
+            self.overflow.iter().filter(|&&item_index| {
+                items[item_index].position().distance_squared(center) <= radius_squared
+            }),
+        );
+
+        output
+    }
+
+    /// the `count` items closest to `point` and their distances, closest first
+    pub fn nearest<T>(&self, point: Vec3, count: usize, items: &[T]) -> Vec<(usize, f32)>
+    where
+        T: PositionSpatial,
+    {
+        nearest_in_tree(
+            self,
+            count,
+            |node_index| self.nodes[node_index].boundary.distance_squared(point),
+            |item_index| items[item_index].position().distance_squared(point),
+        )
+    }
+
+    fn search_range_node<T>(
+        &self, target_node_index: usize, boundary: &BoundingBox3, items: &[T], outputs: &mut Vec<usize>,
+    ) where
+        T: PositionSpatial,
+    {
+        self.walk_with(target_node_index, |node| {
+            if !node.boundary.overlaps(boundary) {
+                return Visit::Prune;
+            }
+
+            outputs.extend(
+                node.items.iter().filter(|&&item_index| boundary.contains(items[item_index].position())),
+            );
+            Visit::Descend
+        });
+    }
+
+    fn search_radius_node<T>(
+        &self, target_node_index: usize, center: Vec3, radius_squared: f32, items: &[T],
+        outputs: &mut Vec<usize>,
+    ) where
+        T: PositionSpatial,
+    {
+        self.walk_with(target_node_index, |node| {
+            if node.boundary.distance_squared(center) > radius_squared {
+                return Visit::Prune;
+            }
+
+            outputs.extend(node.items.iter().filter(|&&item_index| {
+                items[item_index].position().distance_squared(center) <= radius_squared
+            }));
+            Visit::Descend
+        });
+    }
+
+    /// overfull leaves stay leaves once they hit max_depth, or when everything
+    /// in them sits on one point
+    fn can_subdivide<T>(&self, target_node_index: usize, items: &[T]) -> bool
+    where
+        T: PositionSpatial,
+    {
+        if self.nodes[target_node_index].depth >= self.max_depth {
+            return false;
+        }
+
+        holds_distinct_positions(self.leaf_items(target_node_index), |item_index| {
+            items[item_index].position()
+        })
+    }
+
+    fn partition_recursive<T>(&mut self, target_node_index: usize, items: &[T], scratch: &mut [usize])
+    where
+        T: PositionSpatial,
+    {
+        if self.nodes[target_node_index].data_len <= self.leaf_capacity
+            || !self.can_subdivide(target_node_index, items)
+        {
+            return;
+        }
+
+        let node = self.nodes[target_node_index];
+        let Some(data_head) = node.data_head
+        else {
+            return;
+        };
+        let leaf_start = self.subdivide_stem_to_leaf(target_node_index);
+        let run = data_head..(data_head + node.data_len);
+        let octant_of = |item_index: usize| node.boundary.octant_of(items[item_index].position());
+
+        let leaf_runs: [(Option<usize>, usize); Self::STEM_LEAF_COUNT] =
+            partition_run(&mut self.item_indices[run], data_head, scratch, octant_of);
+
+        // the stem hands its whole run down to the leaves
+        self.nodes[target_node_index].data_head = None;
+        self.nodes[target_node_index].data_len = 0;
+        leaf_runs.into_iter().enumerate().for_each(|(octant, (leaf_head, leaf_len))| {
+            let leaf = &mut self.nodes[leaf_start + octant];
+            leaf.data_head = leaf_head;
+            leaf.data_len = leaf_len;
+        });
+
+        (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+            self.partition_recursive(leaf, items, scratch);
+        });
+    }
+
+    /// gives the node 8 empty leaves and returns the index of the first one
+    fn subdivide_stem_to_leaf(&mut self, target_node_index: usize) -> usize {
+        let depth = self.nodes[target_node_index].depth + 1;
+        let octants = self.nodes[target_node_index].boundary.split_octants();
+        self.nodes.extend(octants.map(|octant| OctreeNode::build(octant, depth)));
+
+        let leaf_start = self.nodes.len() - Self::STEM_LEAF_COUNT;
+        self.nodes[target_node_index].leaves = Some(leaf_start);
+
+        leaf_start
+    }
+}
+
+impl TreeHierarchy for Octree {
+    type Boundary = BoundingBox3;
+
+    fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn node_boundary(&self, node_index: usize) -> BoundingBox3 {
+        self.nodes[node_index].boundary
+    }
+
+    fn node_depth(&self, node_index: usize) -> usize {
+        self.nodes[node_index].depth
+    }
+
+    fn node_children(&self, node_index: usize) -> Option<Range<usize>> {
+        self.nodes[node_index].leaves.map(|leaf_start| leaf_start..(leaf_start + Self::STEM_LEAF_COUNT))
+    }
+
+    fn node_items(&self, node_index: usize) -> &[usize] {
+        self.leaf_items(node_index)
+    }
+
+    fn node_size(&self, node_index: usize) -> f32 {
+        self.nodes[node_index].boundary.max_dimension()
+    }
+
+    fn overflow_items(&self) -> &[usize] {
+        &self.overflow
+    }
+}
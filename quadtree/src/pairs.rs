@@ -0,0 +1,118 @@
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+
+impl QuadTree {
+    /// calls `callback(a, b, distance_squared)` once for every unordered pair
+    /// of items at most `distance` apart, with `a < b`. one walk over the tree:
+    /// pairs inside a leaf are checked directly and every pair of sibling
+    /// subtrees is walked together, skipping any two nodes whose boundaries are
+    /// already too far apart. distances are always plain euclidean, `periodic`
+    /// is ignored here
+    pub fn for_each_pair_within<T, F>(&self, distance: f32, items: &[T], mut callback: F)
+    where
+        T: PositionPlanar,
+        F: FnMut(usize, usize, f32),
+    {
+        let distance_squared = distance * distance;
+        let mut report = |a: usize, b: usize, pair_distance_squared: f32| {
+            if pair_distance_squared <= distance_squared {
+                callback(a.min(b), a.max(b), pair_distance_squared);
+            }
+        };
+        self.self_pairs_recursive(Self::ROOT_INDEX, distance_squared, items, &mut report);
+
+        // overflow items aren't in any node, so they go through regular queries
+        self.overflow.iter().enumerate().for_each(|(offset, &overflow_index)| {
+            let position = items[overflow_index].position();
+            let mut in_tree = Vec::new();
+            self.search_radius_node(Self::ROOT_INDEX, position, distance_squared, items, None, &mut in_tree);
+            in_tree.into_iter().for_each(|(item_index, pair_distance_squared)| {
+                report(overflow_index, item_index, pair_distance_squared);
+            });
+            self.overflow[(offset + 1)..].iter().for_each(|&other_index| {
+                report(overflow_index, other_index, position.distance_squared(items[other_index].position()));
+            });
+        });
+    }
+
+    /// every unordered pair of items at most `distance` apart, smaller index
+    /// first
+    pub fn collision_pairs<T>(&self, distance: f32, items: &[T]) -> Vec<(usize, usize)>
+    where
+        T: PositionPlanar,
+    {
+        let mut pairs = Vec::new();
+        self.for_each_pair_within(distance, items, |a, b, _| pairs.push((a, b)));
+
+        pairs
+    }
+
+    /// pairs with both items somewhere below the node
+    fn self_pairs_recursive<T, F>(
+        &self, target_node_index: usize, distance_squared: f32, items: &[T], report: &mut F,
+    ) where
+        T: PositionPlanar,
+        F: FnMut(usize, usize, f32),
+    {
+        if let Some(leaf_start) = self.nodes[target_node_index].leaves {
+            (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+                self.self_pairs_recursive(leaf, distance_squared, items, report);
+                ((leaf + 1)..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|other_leaf| {
+                    self.cross_pairs_recursive(leaf, other_leaf, distance_squared, items, report);
+                });
+            });
+            return;
+        }
+
+        let stored = self.leaf_items(target_node_index);
+        stored.iter().enumerate().for_each(|(offset, &item_index)| {
+            let position = items[item_index].position();
+            stored[(offset + 1)..].iter().for_each(|&other_index| {
+                report(item_index, other_index, position.distance_squared(items[other_index].position()));
+            });
+        });
+    }
+
+    /// pairs with one item below `first` and the other below `second`
+    fn cross_pairs_recursive<T, F>(
+        &self, first: usize, second: usize, distance_squared: f32, items: &[T], report: &mut F,
+    ) where
+        T: PositionPlanar,
+        F: FnMut(usize, usize, f32),
+    {
+        let (first_node, second_node) = (&self.nodes[first], &self.nodes[second]);
+        if first_node.data_head.is_none() && first_node.leaves.is_none()
+            || second_node.data_head.is_none() && second_node.leaves.is_none()
+            || first_node.boundary.distance_squared_to_box(&second_node.boundary) > distance_squared
+        {
+            return;
+        }
+
+        // open up the bigger of the two stems, or whichever one is a stem
+        let split_first = match (first_node.leaves, second_node.leaves) {
+            (Some(_), Some(_)) => first_node.boundary.max_dimension() >= second_node.boundary.max_dimension(),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => {
+                self.leaf_items(first).iter().for_each(|&item_index| {
+                    let position = items[item_index].position();
+                    self.leaf_items(second).iter().for_each(|&other_index| {
+                        report(
+                            item_index,
+                            other_index,
+                            position.distance_squared(items[other_index].position()),
+                        );
+                    });
+                });
+                return;
+            }
+        };
+
+        let (stem, other) = if split_first { (first_node, second) } else { (second_node, first) };
+        if let Some(leaf_start) = stem.leaves {
+            (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+                self.cross_pairs_recursive(leaf, other, distance_squared, items, report);
+            });
+        }
+    }
+}
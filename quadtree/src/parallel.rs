@@ -0,0 +1,148 @@
+use std::thread;
+
+use crate::barnes_hut::MassPoint;
+use crate::quadtree::OutOfBoundsError;
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+use crate::quadtree::QuadTreeNode;
+
+impl QuadTree {
+    /// same as `construct_tree`, but the subtrees are built on up to `threads`
+    /// scoped threads. the finished tree is identical to the serial one, node
+    /// layout included
+    pub fn construct_tree_parallel<T>(&mut self, items: &[T], threads: usize)
+    where
+        T: PositionPlanar + Sync,
+    {
+        let _ = self.try_construct_tree_parallel(items, threads);
+    }
+
+    pub fn try_construct_tree_parallel<T>(
+        &mut self, items: &[T], threads: usize,
+    ) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar + Sync,
+    {
+        self.weights.clear();
+        self.construct_parallel_with_weights(items, threads)
+    }
+
+    /// same as `construct_tree_weighted`, built like `construct_tree_parallel`
+    pub fn construct_tree_parallel_weighted<T>(&mut self, items: &[T], threads: usize)
+    where
+        T: PositionPlanar + MassPoint + Sync,
+    {
+        self.weights.clear();
+        self.weights.extend(items.iter().map(|item| item.mass()));
+        let _ = self.construct_parallel_with_weights(items, threads);
+    }
+
+    /// builds with whatever is in QuadTree->weights right now
+    fn construct_parallel_with_weights<T>(
+        &mut self, items: &[T], threads: usize,
+    ) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar + Sync,
+    {
+        let rejected = self.gather_items(items);
+        let weights = std::mem::take(&mut self.weights);
+        self.partition_parallel(items, &weights, threads.max(1));
+        self.weights = weights;
+
+        // ensure tree is constructed in a logical manner
+        {
+            debug_assert!(self.nodes.len() % 4 == 1); // must add one for the root
+        }
+
+        if rejected.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(OutOfBoundsError { rejected })
+        }
+    }
+
+    /// splits the root once and hands the 4 quadrants out to at most `threads`
+    /// workers, one of them being the calling thread. a worker with more than
+    /// one thread to spare splits its quadrants again the same way
+    fn partition_parallel<T>(&mut self, items: &[T], weights: &[f32], threads: usize)
+    where
+        T: PositionPlanar + Sync,
+    {
+        let mut scratch = vec![0; self.item_indices.len()];
+        if threads == 1 {
+            self.partition_recursive(Self::ROOT_INDEX, items, weights, &mut scratch);
+            return;
+        }
+        let Some(leaf_start) = self.partition_once(Self::ROOT_INDEX, items, weights, &mut scratch)
+        else {
+            return;
+        };
+
+        let workers = threads.min(Self::STEM_LEAF_COUNT);
+        let worker_threads = threads / workers;
+        let mut quadrants: Vec<QuadTree> = (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT))
+            .map(|leaf| self.detach_subtree(leaf))
+            .collect();
+        let quadrants_per_worker = Self::STEM_LEAF_COUNT.div_ceil(workers);
+        thread::scope(|scope| {
+            let mut groups = quadrants.chunks_mut(quadrants_per_worker);
+            let inline = groups.next().expect("there are always 4 quadrants");
+            groups.for_each(|group| {
+                scope.spawn(move || {
+                    group
+                        .iter_mut()
+                        .for_each(|subtree| subtree.partition_parallel(items, weights, worker_threads));
+                });
+            });
+            inline.iter_mut().for_each(|subtree| subtree.partition_parallel(items, weights, worker_threads));
+        });
+
+        // serial construction lays each quadrant's nodes out right after the
+        // previous one's, so appending them in order matches it exactly
+        quadrants.into_iter().enumerate().for_each(|(quadrant, subtree)| {
+            self.attach_subtree(leaf_start + quadrant, subtree);
+        });
+    }
+
+    /// standalone tree with the leaf as its root and a copy of the leaf's items
+    fn detach_subtree(&self, leaf: usize) -> QuadTree {
+        let mut subtree = QuadTree::build(self.leaf_capacity, self.nodes[leaf].boundary)
+            .with_limits(self.max_depth, self.min_cell_size)
+            .with_duplicate_policy(self.duplicate_policy)
+            .with_split_policy(self.split_policy)
+            .with_periodic(self.periodic);
+        subtree.nodes[Self::ROOT_INDEX] = self.nodes[leaf];
+        subtree.item_indices = self.leaf_items(leaf).to_vec();
+        subtree.nodes[Self::ROOT_INDEX].data_head = (!subtree.item_indices.is_empty()).then_some(0);
+
+        subtree
+    }
+
+    /// grafts a subtree from `detach_subtree` back in place of the leaf,
+    /// shifting its node and item offsets into this tree
+    fn attach_subtree(&mut self, leaf: usize, subtree: QuadTree) {
+        let Some(data_offset) = self.nodes[leaf].data_head
+        else {
+            return;
+        };
+        // local node 0 is the leaf itself, everything else goes on the end
+        let node_offset = self.nodes.len() - 1;
+        let shift = |node: &QuadTreeNode| QuadTreeNode {
+            leaves: node.leaves.map(|leaf_start| leaf_start + node_offset),
+            data_head: node.data_head.map(|data_head| data_head + data_offset),
+            ..*node
+        };
+
+        subtree.nodes.iter().map(shift).enumerate().for_each(|(local_index, node)| {
+            if local_index == Self::ROOT_INDEX {
+                self.nodes[leaf] = node;
+            }
+            else {
+                self.nodes.push(node);
+            }
+        });
+        self.item_indices[data_offset..(data_offset + subtree.item_indices.len())]
+            .copy_from_slice(&subtree.item_indices);
+    }
+}
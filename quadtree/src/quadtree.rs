@@ -1,12 +1,29 @@
+use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
 use glam::Vec2;
 
-use crate::state::Particle;
+use crate::aggregate::TreeHierarchy;
+use crate::barnes_hut::MassPoint;
+use crate::traversal::TreeTraversal;
+use crate::traversal::Visit;
 use crate::utils::BoundingBox;
+use crate::utils::EPSILON;
 
 pub trait PositionPlanar {
     fn position(&self) -> Vec2;
 }
 
+impl PositionPlanar for Vec2 {
+    fn position(&self) -> Vec2 {
+        *self
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct QuadTreeNode {
@@ -14,16 +31,76 @@ pub struct QuadTreeNode {
     pub boundary: BoundingBox,
     // the *first* index for the leaves (there are always 4)
     pub leaves: Option<usize>,
-    // index in QuadTree->node_pointers where actual node data lives
+    // index in QuadTree->item_indices where this leaf's items start
     pub data_head: Option<usize>,
+    // how many items the leaf holds, starting at data_head
+    pub data_len: usize,
+    // how many splits away from the root this node is
+    pub depth: usize,
 }
 
 impl QuadTreeNode {
-    pub fn build(boundary: BoundingBox) -> Self {
-        QuadTreeNode { boundary, leaves: None, data_head: None }
+    pub fn build(boundary: BoundingBox, depth: usize) -> Self {
+        QuadTreeNode { boundary, leaves: None, data_head: None, data_len: 0, depth }
     }
 }
 
+/// what happens when an item lands on the exact position of an item already
+/// stored in the leaf
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // coincident items all get stored, a leaf holding only coincident items is
+    // never split since that could never separate them
+    Keep,
+    // only the first item at a position is stored, the rest are dropped
+    Discard,
+}
+
+/// what happens to items whose position is outside of the root boundary (or
+/// isn't finite) when the tree is constructed
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfBoundsPolicy {
+    // silently left out of the tree
+    Drop,
+    // the root boundary is grown until every finite item fits
+    Grow,
+    // kept in QuadTree->overflow, which every query checks as well
+    Overflow,
+    // left out of the tree, but `try_construct_tree` reports them
+    Reject,
+}
+
+/// where a stem's 4 children meet when it gets split
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitPolicy {
+    // the middle of the boundary, the classic quadtree
+    Center,
+    // the median item on each axis (k-d style), so the children get roughly
+    // even counts. keeps clustered trees shallow
+    Median,
+    // the center of mass of the items, only weighted when built with
+    // `construct_tree_weighted`, otherwise every item counts as 1
+    MassWeighted,
+}
+
+/// indices of the items that were left out of the tree under
+/// `OutOfBoundsPolicy::Reject`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfBoundsError {
+    pub rejected: Vec<usize>,
+}
+
+impl fmt::Display for OutOfBoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} items were outside of the quadtree boundary: {:?}", self.rejected.len(), self.rejected)
+    }
+}
+
+impl Error for OutOfBoundsError {}
+
 /// non-tree style quadtree which only uses indices - should be way faster but
 /// is really confusing
 #[repr(C)]
@@ -31,53 +108,460 @@ impl QuadTreeNode {
 pub struct QuadTree {
     // all nodes for the tree
     pub nodes: Vec<QuadTreeNode>,
-    // each vec holds indices in main for its data, one vec for each node
-    pub node_pointers: Vec<Vec<usize>>,
+    // indices in main for the data. every leaf owns one contiguous run of it
+    pub item_indices: Vec<usize>,
+    // slots in item_indices that no leaf points to anymore, left behind by
+    // incremental updates until the next compact
+    pub stale_indices: usize,
     // max number of items in each leaf
     pub leaf_capacity: usize,
+    // leaves this deep are never split and can go over capacity
+    pub max_depth: usize,
+    // same as max_depth but in world units, leaves this small are never split
+    pub min_cell_size: f32,
+    pub duplicate_policy: DuplicatePolicy,
+    pub out_of_bounds_policy: OutOfBoundsPolicy,
+    pub split_policy: SplitPolicy,
+    // queries treat the root boundary as a torus, so they reach across its
+    // edges and measure minimum image distances
+    pub periodic: bool,
+    // items outside of the root when using OutOfBoundsPolicy::Overflow
+    pub overflow: Vec<usize>,
+    // first index of each group of 4 nodes detached by a collapse, reused by
+    // the next subdivide instead of growing QuadTree->nodes
+    pub free_node_groups: Vec<usize>,
+    // per item weights for SplitPolicy::MassWeighted, kept from the last
+    // construction so later splits and grows still see them. empty means every
+    // item weighs 1
+    pub weights: Vec<f32>,
 }
 
 impl QuadTree {
     pub const ROOT_INDEX: usize = 0;
     pub const STEM_LEAF_COUNT: usize = 4; // because it is a 'quad'-tree
+    pub const DEFAULT_MAX_DEPTH: usize = 24;
 
     pub fn build(leaf_capacity: usize, boundary: BoundingBox) -> Self {
-        QuadTree { nodes: vec![QuadTreeNode::build(boundary)], node_pointers: Vec::new(), leaf_capacity }
+        QuadTree {
+            nodes: vec![QuadTreeNode::build(boundary, 0)],
+            item_indices: Vec::new(),
+            stale_indices: 0,
+            leaf_capacity,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            min_cell_size: 0.,
+            duplicate_policy: DuplicatePolicy::Keep,
+            out_of_bounds_policy: OutOfBoundsPolicy::Drop,
+            split_policy: SplitPolicy::Center,
+            periodic: false,
+            overflow: Vec::new(),
+            free_node_groups: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+
+    pub fn with_limits(mut self, max_depth: usize, min_cell_size: f32) -> Self {
+        self.max_depth = max_depth;
+        self.min_cell_size = min_cell_size;
+        self
+    }
+
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    pub fn with_out_of_bounds_policy(mut self, out_of_bounds_policy: OutOfBoundsPolicy) -> Self {
+        self.out_of_bounds_policy = out_of_bounds_policy;
+        self
+    }
+
+    pub fn with_split_policy(mut self, split_policy: SplitPolicy) -> Self {
+        self.split_policy = split_policy;
+        self
+    }
+
+    /// the period is the root boundary, so this doesn't mix well with
+    /// `OutOfBoundsPolicy::Grow`
+    pub fn with_periodic(mut self, periodic: bool) -> Self {
+        self.periodic = periodic;
+        self
     }
 
     pub fn construct_tree<T>(&mut self, items: &[T])
     where
         T: PositionPlanar,
     {
-        self.clear_tree();
-        (0..items.len()).for_each(|index| {
-            self.insert_recursive(Self::ROOT_INDEX, index, items);
-        });
+        // only OutOfBoundsPolicy::Reject ever errors and the tree is still built
+        let _ = self.try_construct_tree(items);
+    }
+
+    pub fn try_construct_tree<T>(&mut self, items: &[T]) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar,
+    {
+        self.weights.clear();
+        self.construct_with_weights(items)
+    }
+
+    /// same as `construct_tree`, but `SplitPolicy::MassWeighted` uses the
+    /// items' masses
+    pub fn construct_tree_weighted<T>(&mut self, items: &[T])
+    where
+        T: PositionPlanar + MassPoint,
+    {
+        let _ = self.try_construct_tree_weighted(items);
+    }
+
+    pub fn try_construct_tree_weighted<T>(&mut self, items: &[T]) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar + MassPoint,
+    {
+        self.weights.clear();
+        self.weights.extend(items.iter().map(|item| item.mass()));
+        self.construct_with_weights(items)
+    }
+
+    /// the point a stem's children meet at, `None` for leaves
+    pub fn split_point(&self, stem: usize) -> Option<Vec2> {
+        let leaf_start = self.nodes[stem].leaves?;
+        // quadrant i starts at the split on x and ends at it on y
+        let first = self.nodes[leaf_start].boundary;
+        Some(Vec2::new(first.min.x, first.max.y))
+    }
+
+    /// builds with whatever is in QuadTree->weights right now
+    fn construct_with_weights<T>(&mut self, items: &[T]) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar,
+    {
+        let rejected = self.gather_items(items);
+
+        // everything starts out in the root, which then gets split up in place
+        let stored_count = self.item_indices.len();
+        if stored_count > 0 {
+            let mut scratch = vec![0; stored_count];
+            self.partition_weighted(Self::ROOT_INDEX, items, &mut scratch);
+        }
 
         // ensure tree is constructed in a logical manner
         {
             debug_assert!(self.nodes.len() % 4 == 1); // must add one for the root
         }
+
+        if rejected.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(OutOfBoundsError { rejected })
+        }
     }
 
+    /// coarse query, returns the contents of every leaf that overlaps the
+    /// boundary so some of the items can be outside of it
     pub fn query_range(&self, boundary: &BoundingBox) -> Vec<usize> {
         let mut output = Vec::new();
-        self.search_recursive(Self::ROOT_INDEX, boundary, &mut output);
+        self.search_node(Self::ROOT_INDEX, &self.query_images(boundary), &mut output);
+        output.extend_from_slice(&self.overflow);
+
+        output
+    }
+
+    /// only returns items actually inside the boundary. needs the same items
+    /// the tree was constructed with
+    pub fn query_range_exact<T>(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        let images = self.query_images(boundary);
+        let domain = self.periodic_domain();
+        let mut output = Vec::new();
+        self.search_exact_node(Self::ROOT_INDEX, &images, items, &mut output);
+        output.extend(self.overflow.iter().filter(|&&item_index| {
+            let position = items[item_index].position();
+            let position = domain.map_or(position, |domain| domain.wrap_point(position));
+            images.iter().any(|image| image.contains(position))
+        }));
+
+        output
+    }
+
+    /// items within `radius` of `center` (euclidean, not a square around it)
+    pub fn query_radius<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        self.query_radius_squared(center, radius, items).into_iter().map(|(index, _)| index).collect()
+    }
+
+    /// same as `query_radius` but also hands back each item's squared
+    /// distance to `center`, since it gets computed anyway
+    pub fn query_radius_squared<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        let radius_squared = radius * radius;
+        let domain = self.periodic_domain();
+        let mut output = Vec::new();
+        self.search_radius_node(
+            Self::ROOT_INDEX,
+            center,
+            radius_squared,
+            items,
+            domain.as_ref(),
+            &mut output,
+        );
+        self.overflow.iter().for_each(|&item_index| {
+            let distance_squared =
+                Self::point_distance_squared(domain.as_ref(), items[item_index].position(), center);
+            if distance_squared <= radius_squared {
+                output.push((item_index, distance_squared));
+            }
+        });
 
         output
     }
 
+    /// the `count` closest items to `point` as (index, distance), closest
+    /// first. nodes are visited in order of how far away their boundary is, so
+    /// anything further than the current worst candidate never gets opened
+    pub fn nearest<T>(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        let domain = self.periodic_domain();
+        nearest_in_tree(
+            self,
+            count,
+            |node_index| {
+                Self::node_distance_squared(domain.as_ref(), &self.nodes[node_index].boundary, point)
+            },
+            |item_index| Self::point_distance_squared(domain.as_ref(), items[item_index].position(), point),
+        )
+    }
+
+    pub fn nearest_one<T>(&self, point: Vec2, items: &[T]) -> Option<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        self.nearest(point, 1, items).first().copied()
+    }
+
     pub fn clear_tree(&mut self) {
         self.nodes[Self::ROOT_INDEX].leaves = None;
         self.nodes[Self::ROOT_INDEX].data_head = None;
+        self.nodes[Self::ROOT_INDEX].data_len = 0;
         self.nodes.truncate(1);
-        self.node_pointers.clear();
+        self.item_indices.clear();
+        self.stale_indices = 0;
+        self.overflow.clear();
+        self.free_node_groups.clear();
+    }
+
+    /// moves the root over to `boundary`. the tree is emptied since its nodes
+    /// don't line up with the new root anymore
+    pub fn set_bounds(&mut self, boundary: BoundingBox) {
+        self.clear_tree();
+        self.nodes[Self::ROOT_INDEX].boundary = boundary;
+    }
+
+    /// the items stored directly in a node, always empty for stems
+    pub fn leaf_items(&self, node_index: usize) -> &[usize] {
+        let node = &self.nodes[node_index];
+        match node.data_head {
+            Some(data_head) => &self.item_indices[data_head..(data_head + node.data_len)],
+            None => &[],
+        }
+    }
+
+    /// rewrites QuadTree->item_indices without the stale slots left behind by
+    /// incremental updates. leaves end up in depth-first order again
+    pub fn compact(&mut self) {
+        let mut compacted = Vec::with_capacity(self.item_indices.len() - self.stale_indices);
+        self.compact_recursive(Self::ROOT_INDEX, &mut compacted);
+        self.item_indices = compacted;
+        self.stale_indices = 0;
+    }
+
+    /// adds a single item to an already constructed tree. `items` has to be the
+    /// same slice the tree was built from, with the new item at `item_index`
+    pub fn insert<T>(&mut self, item_index: usize, items: &[T]) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar,
+    {
+        let position = items[item_index].position();
+        if self.nodes[Self::ROOT_INDEX].boundary.contains(position) {
+            self.insert_recursive(Self::ROOT_INDEX, item_index, items);
+            self.compact_if_stale();
+            return Ok(());
+        }
+
+        match self.out_of_bounds_policy {
+            // no boundary fits a non-finite position, so those are dropped
+            // before the tree gets rebuilt for nothing
+            OutOfBoundsPolicy::Grow if position.is_finite() => self.grow_and_reinsert(item_index, items),
+            OutOfBoundsPolicy::Overflow => self.overflow.push(item_index),
+            OutOfBoundsPolicy::Reject => return Err(OutOfBoundsError { rejected: vec![item_index] }),
+            OutOfBoundsPolicy::Drop | OutOfBoundsPolicy::Grow => {}
+        }
+
+        Ok(())
+    }
+
+    /// same as `insert`, but records the item's mass for
+    /// `SplitPolicy::MassWeighted` first
+    pub fn insert_weighted<T>(&mut self, item_index: usize, items: &[T]) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar + MassPoint,
+    {
+        if self.weights.len() <= item_index {
+            self.weights.resize(item_index + 1, 1.);
+        }
+        self.weights[item_index] = items[item_index].mass();
+        self.insert(item_index, items)
+    }
+
+    /// takes an item back out of the tree, collapsing any stems that end up
+    /// with few enough items to be a single leaf. indices of the other items
+    /// are left alone, so the caller can't shift them around in `items`.
+    /// returns false if the item wasn't in the tree
+    pub fn remove<T>(&mut self, item_index: usize, items: &[T]) -> bool
+    where
+        T: PositionPlanar,
+    {
+        let removed = self.remove_at(item_index, items[item_index].position());
+        self.compact_if_stale();
+
+        removed
+    }
+
+    /// moves an item which used to be at `old_position` to wherever it is now
+    /// in `items`. nothing happens if it is still inside the same leaf
+    pub fn relocate<T>(
+        &mut self, item_index: usize, old_position: Vec2, items: &[T],
+    ) -> Result<(), OutOfBoundsError>
+    where
+        T: PositionPlanar,
+    {
+        let new_position = items[item_index].position();
+        if let Some(leaf) = self.find_leaf(old_position)
+            && self.nodes[leaf].boundary.contains(new_position)
+            && self.duplicate_policy == DuplicatePolicy::Keep
+        {
+            return Ok(());
+        }
+
+        self.remove_at(item_index, old_position);
+        self.insert(item_index, items)
+    }
+
+    /// index of the leaf node whose boundary holds the point
+    pub fn find_leaf(&self, point: Vec2) -> Option<usize> {
+        let mut target_node_index = Self::ROOT_INDEX;
+        if !self.nodes[target_node_index].boundary.contains(point) {
+            return None;
+        }
+
+        while let Some(leaf_start) = self.nodes[target_node_index].leaves {
+            target_node_index = (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT))
+                .find(|&leaf| self.nodes[leaf].boundary.contains(point))?;
+        }
+
+        Some(target_node_index)
     }
 
     pub fn root(&mut self) -> &mut QuadTreeNode {
         &mut self.nodes[Self::ROOT_INDEX]
     }
 
+    /// first step of every bulk construction. clears the tree and puts every
+    /// item that belongs in it into the root, returns the rejected items
+    pub(crate) fn gather_items<T>(&mut self, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        self.gather_indices(0..items.len(), items)
+    }
+
+    /// `gather_items` for only some of the items
+    fn gather_indices<T, I>(&mut self, indices: I, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+        I: Iterator<Item = usize> + Clone,
+    {
+        self.clear_tree();
+        if self.out_of_bounds_policy == OutOfBoundsPolicy::Grow {
+            self.grow_to_fit(indices.clone(), items);
+        }
+
+        let mut rejected = Vec::new();
+        indices.for_each(|index| {
+            if self.nodes[Self::ROOT_INDEX].boundary.contains(items[index].position()) {
+                self.item_indices.push(index);
+                return;
+            }
+
+            match self.out_of_bounds_policy {
+                OutOfBoundsPolicy::Overflow => self.overflow.push(index),
+                OutOfBoundsPolicy::Reject => rejected.push(index),
+                OutOfBoundsPolicy::Drop | OutOfBoundsPolicy::Grow => {}
+            }
+        });
+        if self.duplicate_policy == DuplicatePolicy::Discard {
+            self.discard_duplicates(items);
+        }
+
+        let stored_count = self.item_indices.len();
+        let root = &mut self.nodes[Self::ROOT_INDEX];
+        root.data_head = (stored_count > 0).then_some(0);
+        root.data_len = stored_count;
+
+        rejected
+    }
+
+    fn grow_to_fit<T, I>(&mut self, indices: I, items: &[T])
+    where
+        T: PositionPlanar,
+        I: Iterator<Item = usize>,
+    {
+        let boundary = &mut self.nodes[Self::ROOT_INDEX].boundary;
+        let (mut min, mut max) = (boundary.center(), boundary.center());
+        indices.map(|index| items[index].position()).filter(|position| position.is_finite()).for_each(
+            |position| {
+                min = min.min(position);
+                max = max.max(position);
+            },
+        );
+        if boundary.contains(min) && boundary.contains(max) {
+            return;
+        }
+
+        // the boundary excludes its max edge, so push it a little past the
+        // furthest item
+        boundary.min = boundary.min.min(min);
+        boundary.max = boundary.max.max(max);
+        boundary.max += Vec2::splat(boundary.max_dimension() * 1e-3);
+    }
+
+    /// the root can't grow in place, so the tree gets rebuilt from what it
+    /// holds right now plus the new item. anything removed stays removed
+    fn grow_and_reinsert<T>(&mut self, item_index: usize, items: &[T])
+    where
+        T: PositionPlanar,
+    {
+        let mut stored = Vec::with_capacity(self.item_indices.len() - self.stale_indices + 1);
+        self.collect_node(Self::ROOT_INDEX, &mut stored);
+        stored.extend_from_slice(&self.overflow);
+        stored.push(item_index);
+        stored.sort_unstable();
+
+        self.gather_indices(stored.into_iter(), items);
+        let stored_count = self.item_indices.len();
+        if stored_count > 0 {
+            let mut scratch = vec![0; stored_count];
+            self.partition_weighted(Self::ROOT_INDEX, items, &mut scratch);
+        }
+    }
+
     fn insert_recursive<T>(&mut self, target_node_index: usize, item_index: usize, items: &[T])
     where
         T: PositionPlanar,
@@ -101,133 +585,699 @@ impl QuadTree {
         }
         // if reached, we are dealing with a leaf
 
-        if let Some(node_list_index) = self.nodes[target_node_index].data_head {
-            self.node_pointers[node_list_index].push(item_index);
+        let position = items[item_index].position();
+        let is_duplicate =
+            || self.leaf_items(target_node_index).iter().any(|&other| items[other].position() == position);
+        if self.duplicate_policy == DuplicatePolicy::Discard && is_duplicate() {
+            return;
+        }
+
+        self.push_to_leaf(target_node_index, item_index);
+        if self.nodes[target_node_index].data_len > self.leaf_capacity {
+            let mut scratch = vec![0; self.nodes[target_node_index].data_len];
+            self.partition_weighted(target_node_index, items, &mut scratch);
+        }
+    }
 
-            if self.node_pointers[node_list_index].len() > self.leaf_capacity {
-                self.subdivide_stem_to_leaf(target_node_index, items);
+    /// appends to the leaf's run of item_indices. the run has to be at the very
+    /// end of the buffer to grow, otherwise it gets moved there first
+    fn push_to_leaf(&mut self, target_node_index: usize, item_index: usize) {
+        let node = self.nodes[target_node_index];
+        match node.data_head {
+            Some(data_head) if data_head + node.data_len == self.item_indices.len() => {}
+            Some(data_head) => {
+                let moved_head = self.item_indices.len();
+                self.item_indices.extend_from_within(data_head..(data_head + node.data_len));
+                self.stale_indices += node.data_len;
+                self.nodes[target_node_index].data_head = Some(moved_head);
+            }
+            None => {
+                self.nodes[target_node_index].data_head = Some(self.item_indices.len());
             }
+        }
+
+        self.item_indices.push(item_index);
+        self.nodes[target_node_index].data_len += 1;
+    }
 
-            // if the node is a leaf and has existing data, push new data and EXIT
+    fn compact_if_stale(&mut self) {
+        if self.stale_indices > Self::STEM_LEAF_COUNT * self.leaf_capacity
+            && self.stale_indices * 2 > self.item_indices.len()
+        {
+            self.compact();
+        }
+    }
+
+    fn compact_recursive(&mut self, target_node_index: usize, compacted: &mut Vec<usize>) {
+        if let Some(leaf_start) = self.nodes[target_node_index].leaves {
+            (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+                self.compact_recursive(leaf, compacted);
+            });
             return;
         }
-        {
-            debug_assert!(self.nodes[target_node_index].data_head.is_none());
+
+        if self.nodes[target_node_index].data_head.is_some() {
+            let data_head = compacted.len();
+            compacted.extend_from_slice(self.leaf_items(target_node_index));
+            self.nodes[target_node_index].data_head = Some(data_head);
         }
-        // if reached, we have an empty leaf. need to create a vec of ptr for leaf
+    }
 
-        // yikes this is really confusing
-        let node_index = self.node_pointers.len();
-        self.node_pointers.push(Vec::with_capacity(self.leaf_capacity));
-        self.node_pointers[node_index].push(item_index);
-        self.nodes[target_node_index].data_head = Some(node_index);
+    /// keeps the first (lowest index) item at every position
+    fn discard_duplicates<T>(&mut self, items: &[T])
+    where
+        T: PositionPlanar,
+    {
+        // adding 0 turns -0 into 0, so the bits agree whenever `==` does like
+        // in insert_recursive
+        let key = |item_index: usize| {
+            let position = items[item_index].position() + Vec2::ZERO;
+            (position.x.to_bits(), position.y.to_bits())
+        };
+        let before = self.item_indices.len();
+        self.item_indices.sort_by_key(|&item_index| (key(item_index), item_index));
+        self.item_indices.dedup_by_key(|item_index| key(*item_index));
+        if self.item_indices.len() != before {
+            self.item_indices.sort_unstable();
+        }
     }
 
-    fn search_recursive(&self, target_node_index: usize, boundary: &BoundingBox, outputs: &mut Vec<usize>) {
-        if !self.nodes[target_node_index].boundary.overlaps(boundary) {
-            return;
+    fn remove_at(&mut self, item_index: usize, position: Vec2) -> bool {
+        if let Some(overflow_index) = self.overflow.iter().position(|&other| other == item_index) {
+            self.overflow.swap_remove(overflow_index);
+            return true;
+        }
+
+        self.remove_recursive(Self::ROOT_INDEX, item_index, position)
+    }
+
+    fn remove_recursive(&mut self, target_node_index: usize, item_index: usize, position: Vec2) -> bool {
+        if !self.nodes[target_node_index].boundary.contains(position) {
+            return false;
         }
 
         if let Some(leaf_start) = self.nodes[target_node_index].leaves {
-            (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
-                self.search_recursive(leaf, boundary, outputs);
-            });
+            let removed = (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT))
+                .any(|leaf| self.remove_recursive(leaf, item_index, position));
+            if removed {
+                self.try_collapse(target_node_index);
+            }
+            return removed;
+        }
+
+        let Some(data_head) = self.nodes[target_node_index].data_head
+        else {
+            return false;
+        };
+        let Some(stored_index) =
+            self.leaf_items(target_node_index).iter().position(|&other| other == item_index)
+        else {
+            return false;
+        };
+
+        // swap the removed item to the back of the run and shrink it
+        let node = &mut self.nodes[target_node_index];
+        self.item_indices.swap(data_head + stored_index, data_head + node.data_len - 1);
+        node.data_len -= 1;
+        self.stale_indices += 1;
+        if node.data_len == 0 {
+            node.data_head = None;
+        }
+
+        true
+    }
+
+    /// turns a stem back into a leaf once its 4 leaves fit into one
+    fn try_collapse(&mut self, target_node_index: usize) {
+        let Some(leaf_start) = self.nodes[target_node_index].leaves
+        else {
+            return;
+        };
+        let leaves = leaf_start..(leaf_start + Self::STEM_LEAF_COUNT);
+        if leaves.clone().any(|leaf| self.nodes[leaf].leaves.is_some()) {
             return;
         }
 
-        if let Some(node_list_index) = self.nodes[target_node_index].data_head {
+        let stored_count: usize = leaves.clone().map(|leaf| self.nodes[leaf].data_len).sum();
+        if stored_count > self.leaf_capacity {
+            return;
+        }
+
+        // the leaves' runs get copied into one new run at the end of the buffer
+        let data_head = self.item_indices.len();
+        leaves.for_each(|leaf| {
+            let node = self.nodes[leaf];
+            if let Some(leaf_head) = node.data_head {
+                self.item_indices.extend_from_within(leaf_head..(leaf_head + node.data_len));
+            }
+            self.nodes[leaf].data_head = None;
+            self.nodes[leaf].data_len = 0;
+        });
+        self.stale_indices += stored_count;
+        self.free_node_groups.push(leaf_start);
+
+        let node = &mut self.nodes[target_node_index];
+        node.leaves = None;
+        node.data_head = (stored_count > 0).then_some(data_head);
+        node.data_len = stored_count;
+    }
+
+    /// the root boundary, if queries wrap around it
+    pub fn periodic_domain(&self) -> Option<BoundingBox> {
+        self.periodic.then_some(self.nodes[Self::ROOT_INDEX].boundary)
+    }
+
+    /// the boxes a range query actually has to check, more than one when it
+    /// hangs over the edge of a periodic root
+    fn query_images(&self, boundary: &BoundingBox) -> Vec<BoundingBox> {
+        match self.periodic_domain() {
+            Some(domain) => boundary.wrapped_images(&domain),
+            None => vec![*boundary],
+        }
+    }
+
+    fn point_distance_squared(domain: Option<&BoundingBox>, a: Vec2, b: Vec2) -> f32 {
+        match domain {
+            Some(domain) => domain.wrap_offset(a - b).length_squared(),
+            None => a.distance_squared(b),
+        }
+    }
+
+    fn node_distance_squared(domain: Option<&BoundingBox>, boundary: &BoundingBox, point: Vec2) -> f32 {
+        match domain {
+            Some(domain) => boundary.wrapped_distance_squared(point, domain),
+            None => boundary.distance_squared(point),
+        }
+    }
+
+    fn search_node(&self, target_node_index: usize, boundaries: &[BoundingBox], outputs: &mut Vec<usize>) {
+        self.walk_with(target_node_index, |node| {
+            if !boundaries.iter().any(|boundary| node.boundary.overlaps(boundary)) {
+                return Visit::Prune;
+            }
+
             // iteratively clone data from leaf into output vec
-            outputs.extend_from_slice(&self.node_pointers[node_list_index]);
+            outputs.extend_from_slice(node.items);
+            Visit::Descend
+        });
+    }
+
+    fn search_exact_node<T>(
+        &self, target_node_index: usize, boundaries: &[BoundingBox], items: &[T], outputs: &mut Vec<usize>,
+    ) where
+        T: PositionPlanar,
+    {
+        self.walk_with(target_node_index, |node| {
+            if !boundaries.iter().any(|boundary| node.boundary.overlaps(boundary)) {
+                return Visit::Prune;
+            }
+
+            // whole node is inside the query so nothing needs to be checked
+            if boundaries.iter().any(|boundary| boundary.contains_box(&node.boundary)) {
+                self.collect_node(node.index, outputs);
+                return Visit::Prune;
+            }
+
+            outputs.extend(node.items.iter().filter(|&&item_index| {
+                boundaries.iter().any(|boundary| boundary.contains(items[item_index].position()))
+            }));
+            Visit::Descend
+        });
+    }
+
+    /// `domain` is the periodic root, `None` for plain euclidean distances
+    pub(crate) fn search_radius_node<T>(
+        &self, target_node_index: usize, center: Vec2, radius_squared: f32, items: &[T],
+        domain: Option<&BoundingBox>, outputs: &mut Vec<(usize, f32)>,
+    ) where
+        T: PositionPlanar,
+    {
+        self.walk_with(target_node_index, |node| {
+            if Self::node_distance_squared(domain, &node.boundary, center) > radius_squared {
+                return Visit::Prune;
+            }
+
+            node.items.iter().for_each(|&item_index| {
+                let distance_squared =
+                    Self::point_distance_squared(domain, items[item_index].position(), center);
+                if distance_squared <= radius_squared {
+                    outputs.push((item_index, distance_squared));
+                }
+            });
+            Visit::Descend
+        });
+    }
+
+    fn collect_node(&self, target_node_index: usize, outputs: &mut Vec<usize>) {
+        self.walk_with(target_node_index, |node| {
+            outputs.extend_from_slice(node.items);
+            Visit::Descend
+        });
+    }
+
+    /// overfull leaves stay leaves once they hit the depth or size limit, or
+    /// when everything in them sits on one point
+    pub(crate) fn can_subdivide<T>(&self, target_node_index: usize, items: &[T]) -> bool
+    where
+        T: PositionPlanar,
+    {
+        let node = &self.nodes[target_node_index];
+        if node.depth >= self.max_depth || node.boundary.max_dimension() / 2. < self.min_cell_size {
+            return false;
         }
+
+        holds_distinct_positions(self.leaf_items(target_node_index), |item_index| {
+            items[item_index].position()
+        })
+    }
+
+    /// splits an overfull leaf by counting-sorting its run of item_indices by
+    /// quadrant, so each new leaf gets a slice of the old run with no copying
+    /// into separate allocations. keeps going until every leaf fits
+    pub(crate) fn partition_recursive<T>(
+        &mut self, target_node_index: usize, items: &[T], weights: &[f32], scratch: &mut [usize],
+    ) where
+        T: PositionPlanar,
+    {
+        let Some(leaf_start) = self.partition_once(target_node_index, items, weights, scratch)
+        else {
+            return;
+        };
+        (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+            self.partition_recursive(leaf, items, weights, scratch);
+        });
     }
 
-    fn subdivide_stem_to_leaf<T>(&mut self, target_node_index: usize, items: &[T])
+    /// `partition_recursive` with the tree's own weights
+    fn partition_weighted<T>(&mut self, target_node_index: usize, items: &[T], scratch: &mut [usize])
     where
         T: PositionPlanar,
     {
-        self.nodes[target_node_index].leaves = Some(self.nodes.len());
-
-        let [i, ii, iii, iv] = self.nodes[target_node_index].boundary.split_quadrants();
-        self.nodes.extend([
-            QuadTreeNode::build(i),
-            QuadTreeNode::build(ii),
-            QuadTreeNode::build(iii),
-            QuadTreeNode::build(iv),
-        ]);
+        let weights = std::mem::take(&mut self.weights);
+        self.partition_recursive(target_node_index, items, &weights, scratch);
+        self.weights = weights;
+    }
 
-        if let Some(node_list_index) = self.nodes[target_node_index].data_head {
-            // drains the (now stem) node's data to pour into new leaves
-            let stored_item_indices: Vec<usize> = self.node_pointers[node_list_index].drain(..).collect();
-            {
-                debug_assert!(stored_item_indices.len() == self.leaf_capacity + 1);
-            }
+    /// a single level of `partition_recursive`, returns the first new leaf if
+    /// the node was split
+    pub(crate) fn partition_once<T>(
+        &mut self, target_node_index: usize, items: &[T], weights: &[f32], scratch: &mut [usize],
+    ) -> Option<usize>
+    where
+        T: PositionPlanar,
+    {
+        if self.nodes[target_node_index].data_len <= self.leaf_capacity
+            || !self.can_subdivide(target_node_index, items)
+        {
+            return None;
+        }
+
+        let node = self.nodes[target_node_index];
+        let data_head = node.data_head?;
+        let split = self.choose_split(target_node_index, items, weights);
+        let leaf_start = self.subdivide_stem_to_leaf(target_node_index, split);
+        let run = data_head..(data_head + node.data_len);
+        let quadrant_of =
+            |item_index: usize| BoundingBox::quadrant_around(split, items[item_index].position());
+
+        let leaf_runs: [(Option<usize>, usize); Self::STEM_LEAF_COUNT] =
+            partition_run(&mut self.item_indices[run], data_head, scratch, quadrant_of);
+
+        // the stem hands its whole run down to the leaves
+        self.nodes[target_node_index].data_head = None;
+        self.nodes[target_node_index].data_len = 0;
+        leaf_runs.into_iter().enumerate().for_each(|(quadrant, (leaf_head, leaf_len))| {
+            let leaf = &mut self.nodes[leaf_start + quadrant];
+            leaf.data_head = leaf_head;
+            leaf.data_len = leaf_len;
+        });
+
+        Some(leaf_start)
+    }
 
-            ((self.nodes.len() - Self::STEM_LEAF_COUNT)..self.nodes.len()).for_each(|leaf| {
-                stored_item_indices.iter().for_each(|&item_index| {
-                    self.insert_recursive(leaf, item_index, items);
+    /// where a leaf about to be partitioned should be split, according to the
+    /// split policy. always inside the leaf, and never on an axis' lowest
+    /// item value since that would leave everything on one side of it
+    fn choose_split<T>(&self, target_node_index: usize, items: &[T], weights: &[f32]) -> Vec2
+    where
+        T: PositionPlanar,
+    {
+        let center = self.nodes[target_node_index].boundary.center();
+        let stored = self.leaf_items(target_node_index);
+        let candidate = match self.split_policy {
+            SplitPolicy::Center => return center,
+            SplitPolicy::Median => {
+                let mut xs: Vec<f32> =
+                    stored.iter().map(|&item_index| items[item_index].position().x).collect();
+                let mut ys: Vec<f32> =
+                    stored.iter().map(|&item_index| items[item_index].position().y).collect();
+                let middle = stored.len() / 2;
+                Vec2::new(
+                    *xs.select_nth_unstable_by(middle, f32::total_cmp).1,
+                    *ys.select_nth_unstable_by(middle, f32::total_cmp).1,
+                )
+            }
+            SplitPolicy::MassWeighted => {
+                let weight = |item_index: usize| weights.get(item_index).copied().unwrap_or(1.);
+                let (mut total, mut weighted) = (0., Vec2::ZERO);
+                stored.iter().for_each(|&item_index| {
+                    total += weight(item_index);
+                    weighted += items[item_index].position() * weight(item_index);
                 });
-            });
+                if total <= EPSILON {
+                    center
+                }
+                else {
+                    weighted / total
+                }
+            }
+        };
+
+        let separating = |axis: fn(Vec2) -> f32, candidate: f32| {
+            let values = stored.iter().map(|&item_index| axis(items[item_index].position()));
+            let lowest = values.clone().fold(f32::INFINITY, f32::min);
+            if candidate > lowest {
+                return candidate;
+            }
+            let next = values.filter(|&value| value > lowest).fold(f32::INFINITY, f32::min);
+            if next.is_finite() {
+                next
+            }
+            else {
+                axis(center)
+            }
+        };
+        Vec2::new(separating(|point| point.x, candidate.x), separating(|point| point.y, candidate.y))
+    }
+
+    /// gives the node 4 empty leaves meeting at `split` and returns the index
+    /// of the first one
+    pub(crate) fn subdivide_stem_to_leaf(&mut self, target_node_index: usize, split: Vec2) -> usize {
+        let depth = self.nodes[target_node_index].depth + 1;
+        let [i, ii, iii, iv] = self.nodes[target_node_index].boundary.split_quadrants_at(split);
+        let new_leaves = [
+            QuadTreeNode::build(i, depth),
+            QuadTreeNode::build(ii, depth),
+            QuadTreeNode::build(iii, depth),
+            QuadTreeNode::build(iv, depth),
+        ];
+
+        let leaf_start = match self.free_node_groups.pop() {
+            Some(leaf_start) => {
+                self.nodes[leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)].copy_from_slice(&new_leaves);
+                leaf_start
+            }
+            None => {
+                self.nodes.extend(new_leaves);
+                self.nodes.len() - Self::STEM_LEAF_COUNT
+            }
+        };
+        self.nodes[target_node_index].leaves = Some(leaf_start);
+
+        leaf_start
+    }
+}
+
+/// counting-sorts a node's run of item_indices, starting at `data_head`, by
+/// which of the `N` children each item goes to, so every child ends up with a
+/// slice of the run. returns each child's data_head and data_len
+pub(crate) fn partition_run<const N: usize, F>(
+    run: &mut [usize], data_head: usize, scratch: &mut [usize], child_of: F,
+) -> [(Option<usize>, usize); N]
+where
+    F: Fn(usize) -> usize,
+{
+    let mut counts = [0; N];
+    run.iter().for_each(|&item_index| {
+        counts[child_of(item_index)] += 1;
+    });
+    let mut offsets = [0; N];
+    (1..N).for_each(|child| {
+        offsets[child] = offsets[child - 1] + counts[child - 1];
+    });
+
+    let scratch = &mut scratch[..run.len()];
+    let mut cursors = offsets;
+    run.iter().for_each(|&item_index| {
+        let child = child_of(item_index);
+        scratch[cursors[child]] = item_index;
+        cursors[child] += 1;
+    });
+    run.copy_from_slice(scratch);
+
+    std::array::from_fn(|child| ((counts[child] > 0).then_some(data_head + offsets[child]), counts[child]))
+}
+
+/// false if the items all sit on one point, or there are none. splitting
+/// could never separate those
+pub(crate) fn holds_distinct_positions<P, F>(stored: &[usize], position: F) -> bool
+where
+    P: PartialEq,
+    F: Fn(usize) -> P,
+{
+    let Some(&first) = stored.first()
+    else {
+        return false;
+    };
+    let first = position(first);
+
+    stored.iter().any(|&item_index| position(item_index) != first)
+}
+
+/// best-first k nearest neighbor search over any flat tree: nodes are visited
+/// closest first until the next one is further than the `count`th best item.
+/// `node_distance` and `item_distance` are squared distances to the query.
+/// returns items and their (not squared) distances, closest first
+pub(crate) fn nearest_in_tree<H, N, I>(
+    tree: &H, count: usize, node_distance: N, item_distance: I,
+) -> Vec<(usize, f32)>
+where
+    H: TreeHierarchy,
+    N: Fn(usize) -> f32,
+    I: Fn(usize) -> f32,
+{
+    if count == 0 {
+        return Vec::new();
+    }
+
+    // min-heap of nodes to visit, max-heap of the best items found so far
+    let mut frontier = BinaryHeap::new();
+    let mut best: BinaryHeap<DistanceEntry> = BinaryHeap::with_capacity(count + 1);
+    let offer = |best: &mut BinaryHeap<DistanceEntry>, item_index: usize| {
+        best.push(DistanceEntry::build(item_distance(item_index), item_index));
+        if best.len() > count {
+            best.pop();
         }
+    };
+    tree.overflow_items().iter().for_each(|&item_index| offer(&mut best, item_index));
+    frontier.push(Reverse(DistanceEntry::build(node_distance(QuadTree::ROOT_INDEX), QuadTree::ROOT_INDEX)));
+
+    while let Some(Reverse(entry)) = frontier.pop() {
+        if best.len() == count
+            && best.peek().is_some_and(|worst| entry.distance_squared > worst.distance_squared)
+        {
+            break;
+        }
+
+        match tree.node_children(entry.index) {
+            Some(children) => children.for_each(|child| {
+                frontier.push(Reverse(DistanceEntry::build(node_distance(child), child)));
+            }),
+            None => tree.node_items(entry.index).iter().for_each(|&item_index| offer(&mut best, item_index)),
+        }
+    }
+
+    best.into_sorted_vec().into_iter().map(|entry| (entry.index, entry.distance_squared.sqrt())).collect()
+}
+
+/// heap entry for the nearest neighbor search, `index` is either a node or an
+/// item depending on which heap it is in. the raycast keys its cells by the
+/// distance along the ray instead
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DistanceEntry {
+    pub(crate) distance_squared: f32,
+    pub(crate) index: usize,
+}
+
+impl DistanceEntry {
+    pub(crate) fn build(distance_squared: f32, index: usize) -> Self {
+        DistanceEntry { distance_squared, index }
+    }
+}
+
+impl PartialEq for DistanceEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DistanceEntry {}
+
+impl PartialOrd for DistanceEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DistanceEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared).then(self.index.cmp(&other.index))
+    }
+}
+
+/// the fast index tree bundled together with the items it indexes, so queries
+/// can hand back the items themselves. `S` can either own the items (`Vec<T>`)
+/// or just borrow them (`&[T]`), the tree itself doesn't care
+#[repr(C)]
+#[derive(Debug)]
+pub struct QuadTreeItems<T, S = Vec<T>> {
+    pub tree: QuadTree,
+    pub items: S,
+    marker: PhantomData<T>,
+}
+
+impl<T, S> QuadTreeItems<T, S>
+where
+    T: PositionPlanar,
+    S: AsRef<[T]>,
+{
+    pub fn build(leaf_capacity: usize, boundary: BoundingBox, items: S) -> Self {
+        let mut tree =
+            QuadTreeItems { tree: QuadTree::build(leaf_capacity, boundary), items, marker: PhantomData };
+        tree.construct_tree();
+
+        tree
+    }
+
+    /// has to be called after the items are mutated or moved around
+    pub fn construct_tree(&mut self) {
+        self.tree.construct_tree(self.items.as_ref());
+    }
+
+    pub fn items(&self) -> &[T] {
+        self.items.as_ref()
+    }
+
+    pub fn get(&self, index: usize) -> &T {
+        &self.items()[index]
+    }
+
+    pub fn query_range(&self, boundary: &BoundingBox) -> Vec<&T> {
+        self.query_range_indexed(boundary).into_iter().map(|(item, _)| item).collect()
+    }
+
+    pub fn query_range_indexed(&self, boundary: &BoundingBox) -> Vec<(&T, usize)> {
+        let indices = self.tree.query_range_exact(boundary, self.items());
+        indices.into_iter().map(|index| (self.get(index), index)).collect()
+    }
+
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<&T> {
+        self.query_radius_indexed(center, radius).into_iter().map(|(item, _)| item).collect()
+    }
+
+    pub fn query_radius_indexed(&self, center: Vec2, radius: f32) -> Vec<(&T, usize)> {
+        let indices = self.tree.query_radius(center, radius, self.items());
+        indices.into_iter().map(|index| (self.get(index), index)).collect()
+    }
+
+    pub fn nearest(&self, point: Vec2, count: usize) -> Vec<(&T, usize, f32)> {
+        let found = self.tree.nearest(point, count, self.items());
+        found.into_iter().map(|(index, distance)| (self.get(index), index, distance)).collect()
+    }
+
+    pub fn nearest_one(&self, point: Vec2) -> Option<(&T, usize, f32)> {
+        self.nearest(point, 1).into_iter().next()
+    }
+
+    pub fn into_items(self) -> S {
+        self.items
     }
 }
 
 /// typical tree structure, actually takes ownership of the data. pretty
 /// readable and safe but slow
-#[allow(dead_code)]
 #[repr(C)]
 #[derive(Debug)]
-pub struct QuadTreeOwner {
-    pub points: Vec<Particle>,
-    pub children: Option<[Box<QuadTreeOwner>; 4]>,
+pub struct QuadTreeOwner<T> {
+    pub points: Vec<T>,
+    pub children: Option<[Box<QuadTreeOwner<T>>; 4]>,
     pub capacity: usize,
     pub bounds: BoundingBox,
 }
 
-#[allow(dead_code)]
-impl QuadTreeOwner {
+impl<T> QuadTreeOwner<T>
+where
+    T: PositionPlanar + Clone,
+{
     pub fn build(capacity: usize, bounds: BoundingBox) -> Self {
         QuadTreeOwner { points: Vec::new(), children: None, capacity, bounds }
     }
 
-    pub fn init_tree(&mut self, particles: &[Particle]) {
+    /// empties the tree and moves it over to `bounds`
+    pub fn set_bounds(&mut self, bounds: BoundingBox) {
         self.clear_tree();
-        particles.iter().for_each(|particle| {
-            self.insert_recursive(particle);
+        self.bounds = bounds;
+    }
+
+    pub fn init_tree(&mut self, items: &[T]) {
+        self.clear_tree();
+        items.iter().for_each(|item| {
+            self.insert_recursive(item);
         });
     }
 
-    pub fn query_range(&self, range: &BoundingBox) -> Vec<Particle> {
+    pub fn query_range(&self, range: &BoundingBox) -> Vec<&T> {
         let mut output = Vec::new();
         self.recursive_search(range, &mut output);
         output
     }
 
-    fn insert_recursive(&mut self, particle: &Particle) {
-        if !self.bounds.contains(particle.position) {
+    /// items within `radius` of `center`
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<&T> {
+        let mut output = Vec::new();
+        self.recursive_search_radius(center, radius * radius, &mut output);
+        output
+    }
+
+    /// the `count` items closest to `point` with their distances, closest
+    /// first
+    pub fn nearest(&self, point: Vec2, count: usize) -> Vec<(&T, f32)> {
+        let mut best = Vec::with_capacity(count + 1);
+        if count > 0 {
+            self.recursive_nearest(point, count, &mut best);
+        }
+        best.into_iter().map(|(distance_squared, item)| (item, distance_squared.sqrt())).collect()
+    }
+
+    fn insert_recursive(&mut self, item: &T) {
+        if !self.bounds.contains(item.position()) {
             return;
         }
 
         if let Some(children) = &mut self.children {
             children.iter_mut().for_each(|child| {
-                child.insert_recursive(particle);
+                child.insert_recursive(item);
             });
             return;
         }
 
-        self.points.push(*particle);
-        if self.points.len() > self.capacity {
+        self.points.push(item.clone());
+        // a box too small to split any further just keeps everything
+        let center = self.bounds.center();
+        let can_split = center.cmpgt(self.bounds.min).all() && center.cmplt(self.bounds.max).all();
+        if self.points.len() > self.capacity && can_split {
             self.subdivide();
         }
     }
 
-    fn recursive_search(&self, range: &BoundingBox, outputs: &mut Vec<Particle>) {
+    fn recursive_search<'a>(&'a self, range: &BoundingBox, outputs: &mut Vec<&'a T>) {
         if !self.bounds.overlaps(range) {
             return;
         }
 
-        self.points.iter().for_each(|particle| {
-            if range.contains(particle.position) {
-                outputs.push(*particle);
+        self.points.iter().for_each(|item| {
+            if range.contains(item.position()) {
+                outputs.push(item);
             }
         });
 
@@ -238,6 +1288,59 @@ impl QuadTreeOwner {
         }
     }
 
+    fn recursive_search_radius<'a>(&'a self, center: Vec2, radius_squared: f32, outputs: &mut Vec<&'a T>) {
+        if self.bounds.distance_squared(center) > radius_squared {
+            return;
+        }
+
+        self.points.iter().for_each(|item| {
+            if item.position().distance_squared(center) <= radius_squared {
+                outputs.push(item);
+            }
+        });
+
+        if let Some(children) = &self.children {
+            children.iter().for_each(|child| {
+                child.recursive_search_radius(center, radius_squared, outputs);
+            });
+        }
+    }
+
+    /// `best` stays sorted by squared distance and never grows past `count`
+    fn recursive_nearest<'a>(&'a self, point: Vec2, count: usize, best: &mut Vec<(f32, &'a T)>) {
+        let worst = |best: &Vec<(f32, &'a T)>| {
+            if best.len() < count {
+                f32::INFINITY
+            }
+            else {
+                best[best.len() - 1].0
+            }
+        };
+        if self.bounds.distance_squared(point) > worst(best) {
+            return;
+        }
+
+        self.points.iter().for_each(|item| {
+            let distance_squared = item.position().distance_squared(point);
+            if distance_squared < worst(best) {
+                let slot = best.partition_point(|(other, _)| *other <= distance_squared);
+                best.insert(slot, (distance_squared, item));
+                best.truncate(count);
+            }
+        });
+
+        if let Some(children) = &self.children {
+            let mut order = [0, 1, 2, 3];
+            order.sort_by(|&a, &b| {
+                let a = children[a].bounds.distance_squared(point);
+                a.total_cmp(&children[b].bounds.distance_squared(point))
+            });
+            order.iter().for_each(|&child| {
+                children[child].recursive_nearest(point, count, best);
+            });
+        }
+    }
+
     fn subdivide(&mut self) {
         let quads = self.bounds.split_quadrants();
         self.children = Some([
@@ -247,10 +1350,10 @@ impl QuadTreeOwner {
             Box::new(QuadTreeOwner::build(self.capacity, quads[3])),
         ]);
 
-        self.points.iter().for_each(|particle| {
+        self.points.iter().for_each(|item| {
             if let Some(children) = &mut self.children {
                 children.iter_mut().for_each(|child| {
-                    child.insert_recursive(particle);
+                    child.insert_recursive(item);
                 });
             }
         });
@@ -0,0 +1,204 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use glam::Vec2;
+
+use crate::aggregate::aggregate_into;
+use crate::aggregate::merge_bounds;
+use crate::aggregate::NodeAggregate;
+use crate::quadtree::DistanceEntry;
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+use crate::utils::BoundingBox;
+
+/// items that are circles around their position
+pub trait RadiusPlanar: PositionPlanar {
+    fn radius(&self) -> f32;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub index: usize,
+    // distance along the (normalized) ray to where it enters the item
+    pub t: f32,
+}
+
+/// tightest box around the circles of the items below the node, `None` if
+/// there aren't any. items are only stored by their center so they can poke
+/// out of their node, rays get tested against this instead
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ItemExtent(pub Option<BoundingBox>);
+
+impl<T> NodeAggregate<T> for ItemExtent
+where
+    T: RadiusPlanar,
+{
+    fn from_items(items: &[T], item_indices: &[usize]) -> Self {
+        item_indices.iter().fold(ItemExtent(None), |extent, &item_index| {
+            let item = &items[item_index];
+            let radius = Vec2::splat(item.radius());
+            ItemExtent(merge_bounds(
+                extent.0,
+                Some(BoundingBox::build(item.position() - radius, item.position() + radius)),
+            ))
+        })
+    }
+
+    fn combine(children: &[Self]) -> Self {
+        ItemExtent(children.iter().fold(None, |extent, child| merge_bounds(extent, child.0)))
+    }
+}
+
+/// casts rays against the items of a `QuadTree`. like `BarnesHutWrapper` it
+/// has to be built again whenever the tree is, any number of rays can be cast
+/// in between
+#[derive(Debug, Default)]
+pub struct RayCaster {
+    // lines up with the tree's nodes
+    pub extents: Vec<ItemExtent>,
+}
+
+impl RayCaster {
+    pub fn new() -> Self {
+        RayCaster { extents: Vec::new() }
+    }
+
+    pub fn build_hierarchy<T>(&mut self, tree: &QuadTree, items: &[T])
+    where
+        T: RadiusPlanar,
+    {
+        aggregate_into(tree, items, &mut self.extents);
+    }
+
+    /// first item hit by the ray within `max_t`. cells are opened front to
+    /// back and the walk stops as soon as nothing left can be closer than the
+    /// best hit so far
+    pub fn raycast<T>(
+        &self, tree: &QuadTree, origin: Vec2, direction: Vec2, max_t: f32, items: &[T],
+    ) -> Option<RayHit>
+    where
+        T: RadiusPlanar,
+    {
+        self.cast(tree, origin, direction, max_t, true, items).first().copied()
+    }
+
+    /// every item hit by the ray within `max_t`, closest first
+    pub fn raycast_all<T>(
+        &self, tree: &QuadTree, origin: Vec2, direction: Vec2, max_t: f32, items: &[T],
+    ) -> Vec<RayHit>
+    where
+        T: RadiusPlanar,
+    {
+        self.cast(tree, origin, direction, max_t, false, items)
+    }
+
+    /// first item hit on the way from `start` to `end`, `t` is measured from
+    /// `start` in world units
+    pub fn segment_cast<T>(&self, tree: &QuadTree, start: Vec2, end: Vec2, items: &[T]) -> Option<RayHit>
+    where
+        T: RadiusPlanar,
+    {
+        self.raycast(tree, start, end - start, start.distance(end), items)
+    }
+
+    fn cast<T>(
+        &self, tree: &QuadTree, origin: Vec2, direction: Vec2, max_t: f32, first_only: bool, items: &[T],
+    ) -> Vec<RayHit>
+    where
+        T: RadiusPlanar,
+    {
+        let Some(direction) = direction.try_normalize()
+        else {
+            return Vec::new();
+        };
+        let inverse_direction = direction.recip();
+        let cell_entry = |node_index: usize, limit: f32| {
+            self.extents[node_index].0?.ray_entry(origin, inverse_direction, limit)
+        };
+
+        let mut limit = max_t;
+        let mut hits = Vec::new();
+        let mut test_item = |item_index: usize, limit: &mut f32| {
+            let item = &items[item_index];
+            if let Some(t) = circle_entry(origin, direction, item.position(), item.radius())
+                && t <= *limit
+            {
+                hits.push(RayHit { index: item_index, t });
+                if first_only {
+                    *limit = t;
+                }
+            }
+        };
+
+        tree.overflow.iter().for_each(|&item_index| test_item(item_index, &mut limit));
+
+        // min-heap of cells keyed by where the ray enters them, which takes the
+        // place of the squared distance
+        let mut frontier = BinaryHeap::new();
+        if let Some(t) = cell_entry(QuadTree::ROOT_INDEX, limit) {
+            frontier.push(Reverse(DistanceEntry::build(t, QuadTree::ROOT_INDEX)));
+        }
+
+        while let Some(Reverse(entry)) = frontier.pop() {
+            if entry.distance_squared > limit {
+                break;
+            }
+
+            if let Some(leaf_start) = tree.nodes[entry.index].leaves {
+                (leaf_start..(leaf_start + QuadTree::STEM_LEAF_COUNT)).for_each(|leaf| {
+                    if let Some(t) = cell_entry(leaf, limit) {
+                        frontier.push(Reverse(DistanceEntry::build(t, leaf)));
+                    }
+                });
+            }
+            else {
+                tree.leaf_items(entry.index).iter().for_each(|&item_index| test_item(item_index, &mut limit));
+            }
+        }
+
+        hits.retain(|hit| hit.t <= limit);
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t).then(a.index.cmp(&b.index)));
+        hits
+    }
+}
+
+impl QuadTree {
+    /// `RayCaster::raycast` for a single ray, the item extents are built
+    /// first. keep a `RayCaster` around to cast many rays at the same tree
+    pub fn raycast<T>(&self, origin: Vec2, direction: Vec2, max_t: f32, items: &[T]) -> Option<RayHit>
+    where
+        T: RadiusPlanar,
+    {
+        let mut caster = RayCaster::new();
+        caster.build_hierarchy(self, items);
+        caster.raycast(self, origin, direction, max_t, items)
+    }
+
+    /// `RayCaster::raycast_all` for a single ray
+    pub fn raycast_all<T>(&self, origin: Vec2, direction: Vec2, max_t: f32, items: &[T]) -> Vec<RayHit>
+    where
+        T: RadiusPlanar,
+    {
+        let mut caster = RayCaster::new();
+        caster.build_hierarchy(self, items);
+        caster.raycast_all(self, origin, direction, max_t, items)
+    }
+}
+
+/// distance along a normalized ray to where it enters the circle, 0 if it
+/// starts inside
+fn circle_entry(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let along = offset.dot(direction);
+    let outside = offset.length_squared() - radius * radius;
+    if outside <= 0. {
+        return Some(0.);
+    }
+    if along > 0. {
+        return None;
+    }
+
+    let discriminant = along * along - outside;
+    (discriminant >= 0.).then(|| -along - discriminant.sqrt())
+}
@@ -7,11 +7,13 @@ use glam::Vec3;
 
 use sokol::gfx;
 
+use quadtree::quadtree::QuadTree;
+use quadtree::state::State;
+use quadtree::traversal::TreeTraversal;
+
 use crate::compiled_shaders::circ_shader;
 use crate::compiled_shaders::line_shader;
 use crate::compiled_shaders::tri_shader;
-use crate::quadtree::QuadTree;
-use crate::state::State;
 
 #[allow(dead_code)]
 #[repr(C)]
@@ -85,26 +87,18 @@ impl PrimitiveRenderer {
             gfx::draw(0, instances.len() / target.draw_elements, 1);
 
             fn quad_centers(quadtree: &QuadTree, data: &mut Vec<f32>) {
-                traverse_recursive(quadtree, QuadTree::ROOT_INDEX, data);
-
-                fn traverse_recursive(quadtree: &QuadTree, target_node_index: usize, data: &mut Vec<f32>) {
-                    if let Some(leaf_start) = quadtree.nodes[target_node_index].leaves {
-                        (leaf_start..(leaf_start + QuadTree::STEM_LEAF_COUNT)).for_each(|leaf| {
-                            traverse_recursive(quadtree, leaf, data);
-                        });
-
-                        let node = quadtree.nodes[target_node_index];
-                        let center = node.boundary.center();
-                        let (min, max) = (node.boundary.min, node.boundary.max);
-                        #[rustfmt::skip]
-                        data.extend_from_slice(&[
-                            center.x, min.y, 1., 0.7, 0.7,
-                            center.x, max.y, 1., 0.7, 0.7,
-                            min.x, center.y, 1., 0.7, 0.7,
-                            max.x, center.y, 1., 0.7, 0.7,
-                        ]);
-                    }
-                }
+                quadtree.pre_order().filter(|node| !node.is_leaf()).for_each(|node| {
+                    // stems don't always split at their center, draw where they actually do
+                    let split = quadtree.split_point(node.index).unwrap_or(node.boundary.center());
+                    let (min, max) = (node.boundary.min, node.boundary.max);
+                    #[rustfmt::skip]
+                    data.extend_from_slice(&[
+                        split.x, min.y, 1., 0.7, 0.7,
+                        split.x, max.y, 1., 0.7, 0.7,
+                        min.x, split.y, 1., 0.7, 0.7,
+                        max.x, split.y, 1., 0.7, 0.7,
+                    ]);
+                });
             }
         }
 
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use glam::IVec2;
+use glam::Vec2;
+
+use crate::grid::CellGrid;
+use crate::quadtree::PositionPlanar;
+use crate::spatial_index::SpatialIndex;
+use crate::utils::BoundingBox;
+
+/// sparse grid of `cell_size` squares lined up with `bounds.min`, only the
+/// cells that actually hold something get stored. unlike `UniformGrid` the
+/// cells keep going past `bounds`, so items anywhere are fine. works best when
+/// the items are spread out evenly and the queries are about one cell big.
+/// the queries are shared with `UniformGrid` through `CellGrid`
+#[repr(C)]
+#[derive(Debug)]
+pub struct SpatialHash {
+    pub bounds: BoundingBox,
+    pub cell_size: f32,
+    pub cells: HashMap<IVec2, Vec<usize>>,
+    // smallest and largest cell coordinates that hold anything
+    pub occupied_min: IVec2,
+    pub occupied_max: IVec2,
+}
+
+impl SpatialHash {
+    pub fn build(cell_size: f32, bounds: BoundingBox) -> Self {
+        SpatialHash {
+            bounds,
+            cell_size,
+            cells: HashMap::new(),
+            occupied_min: IVec2::MAX,
+            occupied_max: IVec2::MIN,
+        }
+    }
+
+    pub fn cell_of(&self, point: Vec2) -> IVec2 {
+        ((point - self.bounds.min) / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn cell_items(&self, cell: IVec2) -> &[usize] {
+        self.cells.get(&cell).map_or(&[], |cell_items| cell_items.as_slice())
+    }
+
+    /// cells are lined up with `bounds.min`, so moving it empties them until
+    /// the next `construct_hash`
+    pub fn set_bounds(&mut self, bounds: BoundingBox) {
+        self.bounds = bounds;
+        self.clear();
+    }
+
+    /// empties the cells but keeps their allocations around for the next build
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(|cell_items| cell_items.clear());
+        self.occupied_min = IVec2::MAX;
+        self.occupied_max = IVec2::MIN;
+    }
+
+    pub fn construct_hash<T>(&mut self, items: &[T])
+    where
+        T: PositionPlanar,
+    {
+        self.clear();
+        items.iter().enumerate().for_each(|(item_index, item)| {
+            let cell = self.cell_of(item.position());
+            self.occupied_min = self.occupied_min.min(cell);
+            self.occupied_max = self.occupied_max.max(cell);
+            self.cells.entry(cell).or_default().push(item_index);
+        });
+        // cells that stayed empty since the last build would only slow down
+        // iteration over the map
+        self.cells.retain(|_, cell_items| !cell_items.is_empty());
+    }
+
+    pub fn query_range<T>(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        CellGrid::query_range(self, boundary, items)
+    }
+
+    pub fn query_radius<T>(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>
+    where
+        T: PositionPlanar,
+    {
+        CellGrid::query_radius(self, center, radius, items)
+    }
+
+    pub fn nearest<T>(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        CellGrid::nearest(self, point, count, items)
+    }
+
+    pub fn nearest_one<T>(&self, point: Vec2, items: &[T]) -> Option<(usize, f32)>
+    where
+        T: PositionPlanar,
+    {
+        self.nearest(point, 1, items).first().copied()
+    }
+}
+
+impl CellGrid for SpatialHash {
+    fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell_coordinates(&self, point: Vec2) -> IVec2 {
+        self.cell_of(point)
+    }
+
+    fn occupied_cells(&self) -> Option<(IVec2, IVec2)> {
+        (!self.cells.is_empty()).then_some((self.occupied_min, self.occupied_max))
+    }
+
+    fn items_at(&self, cell: IVec2) -> &[usize] {
+        self.cell_items(cell)
+    }
+
+    fn stored_cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn for_each_stored_cell<F>(&self, mut callback: F)
+    where
+        F: FnMut(IVec2, &[usize]),
+    {
+        self.cells.iter().for_each(|(&cell, cell_items)| callback(cell, cell_items));
+    }
+}
+
+impl<T> SpatialIndex<T> for SpatialHash
+where
+    T: PositionPlanar,
+{
+    fn build(&mut self, items: &[T]) {
+        self.construct_hash(items);
+    }
+
+    fn set_bounds(&mut self, bounds: BoundingBox) {
+        SpatialHash::set_bounds(self, bounds);
+    }
+
+    fn query_box(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize> {
+        self.query_range(boundary, items)
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize> {
+        SpatialHash::query_radius(self, center, radius, items)
+    }
+
+    fn nearest(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)> {
+        SpatialHash::nearest(self, point, count, items)
+    }
+}
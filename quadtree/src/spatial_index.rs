@@ -0,0 +1,150 @@
+use glam::Vec2;
+
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+use crate::quadtree::QuadTreeOwner;
+use crate::utils::BoundingBox;
+
+/// common interface over the different neighbor search structures, so they
+/// can be swapped out and compared. every backend hands back indices into the
+/// `items` slice it was built from, and `items` has to be passed back in
+/// unchanged for the queries
+pub trait SpatialIndex<T>
+where
+    T: PositionPlanar,
+{
+    /// throws away whatever was indexed before and indexes `items`
+    fn build(&mut self, items: &[T]);
+
+    /// moves the index over to new bounds. whatever was indexed is thrown
+    /// away until the next `build`
+    fn set_bounds(&mut self, bounds: BoundingBox);
+
+    /// items inside `boundary`
+    fn query_box(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize>;
+
+    /// items within `radius` of `center`
+    fn query_radius(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize>;
+
+    /// the `count` items closest to `point` and their distances, closest first
+    fn nearest(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)>;
+
+    fn nearest_one(&self, point: Vec2, items: &[T]) -> Option<(usize, f32)> {
+        self.nearest(point, 1, items).first().copied()
+    }
+}
+
+impl<T> SpatialIndex<T> for QuadTree
+where
+    T: PositionPlanar,
+{
+    fn build(&mut self, items: &[T]) {
+        self.construct_tree(items);
+    }
+
+    fn set_bounds(&mut self, bounds: BoundingBox) {
+        QuadTree::set_bounds(self, bounds);
+    }
+
+    fn query_box(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize> {
+        self.query_range_exact(boundary, items)
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize> {
+        QuadTree::query_radius(self, center, radius, items)
+    }
+
+    fn nearest(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)> {
+        QuadTree::nearest(self, point, count, items)
+    }
+}
+
+/// what `QuadTreeOwner` keeps a copy of when it is used as a `SpatialIndex`,
+/// just enough to answer queries with indices
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IndexedPoint {
+    pub index: usize,
+    pub position: Vec2,
+}
+
+impl PositionPlanar for IndexedPoint {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
+}
+
+/// items outside of the owner's bounds are dropped, same as `init_tree`
+impl<T> SpatialIndex<T> for QuadTreeOwner<IndexedPoint>
+where
+    T: PositionPlanar,
+{
+    fn build(&mut self, items: &[T]) {
+        let points: Vec<IndexedPoint> = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| IndexedPoint { index, position: item.position() })
+            .collect();
+        self.init_tree(&points);
+    }
+
+    fn set_bounds(&mut self, bounds: BoundingBox) {
+        QuadTreeOwner::set_bounds(self, bounds);
+    }
+
+    fn query_box(&self, boundary: &BoundingBox, _items: &[T]) -> Vec<usize> {
+        self.query_range(boundary).into_iter().map(|point| point.index).collect()
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, _items: &[T]) -> Vec<usize> {
+        QuadTreeOwner::query_radius(self, center, radius).into_iter().map(|point| point.index).collect()
+    }
+
+    fn nearest(&self, point: Vec2, count: usize, _items: &[T]) -> Vec<(usize, f32)> {
+        QuadTreeOwner::nearest(self, point, count)
+            .into_iter()
+            .map(|(item, distance)| (item.index, distance))
+            .collect()
+    }
+}
+
+/// checks every item on every query. slow, but it is the reference the other
+/// backends get compared against
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct BruteForce {
+    pub item_count: usize,
+}
+
+impl<T> SpatialIndex<T> for BruteForce
+where
+    T: PositionPlanar,
+{
+    fn build(&mut self, items: &[T]) {
+        self.item_count = items.len();
+    }
+
+    // there is nothing spatial to resize
+    fn set_bounds(&mut self, _bounds: BoundingBox) {}
+
+    fn query_box(&self, boundary: &BoundingBox, items: &[T]) -> Vec<usize> {
+        (0..self.item_count).filter(|&index| boundary.contains(items[index].position())).collect()
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, items: &[T]) -> Vec<usize> {
+        let radius_squared = radius * radius;
+        (0..self.item_count)
+            .filter(|&index| items[index].position().distance_squared(center) <= radius_squared)
+            .collect()
+    }
+
+    fn nearest(&self, point: Vec2, count: usize, items: &[T]) -> Vec<(usize, f32)> {
+        let mut found: Vec<(usize, f32)> = (0..self.item_count)
+            .map(|index| (index, items[index].position().distance_squared(point)))
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        found.truncate(count);
+
+        found.into_iter().map(|(index, distance_squared)| (index, distance_squared.sqrt())).collect()
+    }
+}
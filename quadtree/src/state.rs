@@ -1,13 +1,19 @@
-use glam::Vec2;
+use std::thread;
 
-use sokol::app as sapp;
+use glam::Vec2;
 
 use crate::barnes_hut::BarnesHutWrapper;
+use crate::barnes_hut::ForceParameters;
+use crate::barnes_hut::MassPoint;
+use crate::fmm::FmmSolver;
+use crate::loose::BoundedPlanar;
+use crate::quadtree::OutOfBoundsPolicy;
 use crate::quadtree::PositionPlanar;
 use crate::quadtree::QuadTree;
-use crate::utils::mouse_to_screen;
+use crate::quadtree::SplitPolicy;
+use crate::raycast::RadiusPlanar;
+use crate::spatial_index::SpatialIndex;
 use crate::utils::positive_rand_range_vec2;
-use crate::utils::wait;
 use crate::utils::zero_centered_range_vec2;
 use crate::utils::BoundingBox;
 
@@ -48,40 +54,89 @@ impl Particle {
     }
 }
 
+impl MassPoint for Particle {
+    type Vector = Vec2;
+
+    fn mass_position(&self) -> Vec2 {
+        self.position
+    }
+
+    fn mass(&self) -> f32 {
+        self.mass
+    }
+}
+
+impl RadiusPlanar for Particle {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+}
+
 impl PositionPlanar for Particle {
     fn position(&self) -> Vec2 {
         self.position
     }
 }
 
+impl BoundedPlanar for Particle {
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::build(self.position - Vec2::splat(self.radius), self.position + Vec2::splat(self.radius))
+    }
+}
+
+/// `I` answers the neighbor queries (`update`, `pick_particle`), e.g. a
+/// `SpatialHash` with config.neighbor_distance wide cells for evenly spread
+/// particles. barnes-hut always runs on `quadtree` since it needs the node
+/// hierarchy
 #[repr(C)]
 #[derive(Debug)]
-pub struct State {
+pub struct State<I = QuadTree> {
     pub dimensions: BoundingBox,
     pub particles: Vec<Particle>,
     pub config: SimulationConfig,
     pub quadtree: QuadTree,
+    pub neighbor_index: I,
 }
 
 impl State {
     pub fn build(width: i32, height: i32) -> Self {
+        let neighbor_index =
+            QuadTree::build(3, BoundingBox::build(Vec2::ZERO, Vec2::new(width as f32, height as f32)))
+                .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow);
+        State::build_with_index(width, height, neighbor_index)
+    }
+}
+
+impl<I> State<I>
+where
+    I: SpatialIndex<Particle> + Sync,
+{
+    pub fn build_with_index(width: i32, height: i32, neighbor_index: I) -> Self {
+        let config = SimulationConfig {
+            starting_spawn: 10000,
+            gravity: 1e2,
+            epsilon_squared: 10.,
+            theta: 2_f32.sqrt() / 2.,
+            velocity_rand_max: 50.,
+            mass_rand_max: 100.,
+            frame_time_dt_mod: 0.1,
+            neighbor_distance: 300.,
+            z_order_particles: false,
+            threads: 1,
+            quadrupole_moments: false,
+            force_solver: ForceSolver::BarnesHut,
+            fmm_order: FmmSolver::DEFAULT_ORDER,
+        };
         State {
             dimensions: BoundingBox::build(Vec2::ZERO, Vec2::new(width as f32, height as f32)),
             particles: Vec::new(),
-            config: SimulationConfig {
-                starting_spawn: 10000,
-                gravity: 1e2,
-                epsilon_squared: 10.,
-                theta: 2_f32.sqrt() / 2.,
-                velocity_rand_max: 50.,
-                mass_rand_max: 100.,
-                frame_time_dt_mod: 0.1,
-                neighbor_distance: 300.,
-            },
+            config,
             quadtree: QuadTree::build(
                 3,
                 BoundingBox::build(Vec2::ZERO, Vec2::new(width as f32, height as f32)),
-            ),
+            )
+            .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow),
+            neighbor_index,
         }
     }
 
@@ -93,24 +148,47 @@ impl State {
         self.particles.push(Particle::new(self.dimensions.max / 2., Vec2::ZERO, 10000.));
     }
 
-    pub fn handle_event(&mut self, event: sapp::Event) {
-        if event.mouse_button == sapp::Mousebutton::Left && event._type == sapp::EventType::MouseDown {
-            self.particles.push(Particle::new(
-                mouse_to_screen(event.mouse_x, event.mouse_y, &self.dimensions),
-                Vec2::ZERO,
-                self.config.mass_rand_max,
-            ));
-            // small delay to prevent like 100 particles spawning and stack overflow
-            wait(5);
-        }
-        if event.key_code == sapp::Keycode::R {
-            self.particles.clear();
-        }
+    pub fn spawn_particle(&mut self, position: Vec2) {
+        self.particles.push(Particle::new(position, Vec2::ZERO, self.config.mass_rand_max));
+    }
+
+    /// index of the particle closest to `position`, if it is within the
+    /// particle's own radius
+    pub fn pick_particle(&mut self, position: Vec2) -> Option<usize> {
+        self.neighbor_index.build(&self.particles);
+        let (index, distance) = self.neighbor_index.nearest_one(position, &self.particles)?;
+
+        (distance <= self.particles[index].radius).then_some(index)
+    }
+
+    /// every pair of particles whose circles overlap, smaller index first
+    pub fn overlapping_particles(&mut self) -> Vec<(usize, usize)> {
+        self.init_tree();
+        let max_radius =
+            self.particles.iter().fold(0_f32, |max_radius, particle| max_radius.max(particle.radius));
+        let mut pairs = Vec::new();
+        self.quadtree.for_each_pair_within(2. * max_radius, &self.particles, |a, b, distance_squared| {
+            let touching = self.particles[a].radius + self.particles[b].radius;
+            if distance_squared <= touching * touching {
+                pairs.push((a, b));
+            }
+        });
+
+        pairs
+    }
+
+    pub fn remove_particle(&mut self, index: usize) -> Particle {
+        self.particles.swap_remove(index)
+    }
+
+    pub fn clear_particles(&mut self) {
+        self.particles.clear();
     }
 
     pub fn update_dimensions(&mut self, width: f32, height: f32) {
         self.dimensions.max = Vec2::new(width, height);
-        self.quadtree.root().boundary = self.dimensions;
+        self.quadtree.set_bounds(self.dimensions);
+        self.neighbor_index.set_bounds(self.dimensions);
     }
 
     #[allow(dead_code)]
@@ -118,13 +196,11 @@ impl State {
     pub fn update(&mut self, mut dt: f32) {
         dt *= self.config.frame_time_dt_mod;
 
-        self.init_tree();
+        self.neighbor_index.build(&self.particles);
         for target_index in 0..self.particles.len() {
-            let neighbors = Self::query_tree(
-                &self.quadtree,
-                self.particles[target_index].position,
-                self.config.neighbor_distance,
-            );
+            let position = self.particles[target_index].position;
+            let neighbors =
+                self.neighbor_index.query_radius(position, self.config.neighbor_distance, &self.particles);
 
             for other_index in neighbors {
                 if target_index == other_index {
@@ -152,12 +228,20 @@ impl State {
         dt *= self.config.frame_time_dt_mod;
 
         self.init_tree();
-        let mut barnes_hut = BarnesHutWrapper::new();
-        barnes_hut.build_hierarchy(&self.quadtree, &self.particles);
-
-        (0..self.particles.len()).for_each(|target_index| {
-            let acceleration = self.barnes_hut_force_recursive(&barnes_hut, target_index);
-            self.particles[target_index].acceleration = acceleration;
+        let accelerations = match self.config.force_solver {
+            ForceSolver::BarnesHut => {
+                let mut barnes_hut = BarnesHutWrapper::new().with_quadrupole(self.config.quadrupole_moments);
+                barnes_hut.build_hierarchy(&self.quadtree, &self.particles);
+                self.barnes_hut_accelerations(&barnes_hut)
+            }
+            ForceSolver::Fmm => FmmSolver::build(self.config.fmm_order).accelerations(
+                &self.quadtree,
+                &self.particles,
+                self.config.force_parameters(),
+            ),
+        };
+        self.particles.iter_mut().zip(accelerations).for_each(|(particle, acceleration)| {
+            particle.acceleration = acceleration;
         });
 
         self.particles.iter_mut().for_each(|particle| {
@@ -174,54 +258,69 @@ impl State {
         ));
     }
 
+    /// the morton builder behind `z_order_particles` is serial and only does
+    /// center splits, so for those it takes precedence over `config.threads`.
+    /// everything else is built on `config.threads` threads
     fn init_tree(&mut self) {
-        self.quadtree.construct_tree(&self.particles);
-    }
-
-    fn query_tree(tree: &QuadTree, pos: Vec2, radius: f32) -> Vec<usize> {
-        tree.query_range(&BoundingBox::build(pos - Vec2::splat(radius), pos + Vec2::splat(radius)))
-    }
-
-    fn barnes_hut_force_recursive(&mut self, barnes_hut: &BarnesHutWrapper, target_index: usize) -> Vec2 {
-        let mut acceleration = Vec2::ZERO;
-        self.calculate_force_recursive(
-            QuadTree::ROOT_INDEX,
-            target_index,
-            &self.quadtree,
-            barnes_hut,
-            &mut acceleration,
-        );
+        let threads = self.config.threads;
+        match self.quadtree.split_policy {
+            // only this split policy looks at the masses
+            SplitPolicy::MassWeighted => {
+                self.quadtree.construct_tree_parallel_weighted(&self.particles, threads);
+            }
+            SplitPolicy::Center if self.config.z_order_particles => {
+                self.quadtree.construct_tree_morton(&self.particles);
+            }
+            _ => self.quadtree.construct_tree_parallel(&self.particles, threads),
+        }
 
-        acceleration
+        if self.config.z_order_particles {
+            self.quadtree.reorder_items(&mut self.particles);
+        }
     }
 
-    fn calculate_force_recursive(
-        &self, node_index: usize, target_index: usize, tree: &QuadTree, barnes_hut: &BarnesHutWrapper,
-        acceleration: &mut Vec2,
-    ) {
-        let Some(node_data) = barnes_hut.barnes_hut_data[node_index]
-        else {
-            return;
-        };
-        let target_particle = self.particles[target_index];
-
-        let pointing = node_data.mass_center - target_particle.position;
-        let sq_radius = pointing.length_squared();
-        if sq_radius < self.config.epsilon_squared {
-            return;
+    /// every particle's acceleration, split across `config.threads` scoped
+    /// threads. each particle is still summed in the same order as the serial
+    /// path so the results are identical
+    fn barnes_hut_accelerations(&self, barnes_hut: &BarnesHutWrapper) -> Vec<Vec2> {
+        let mut accelerations = vec![Vec2::ZERO; self.particles.len()];
+        if self.config.threads <= 1 || self.particles.is_empty() {
+            accelerations.iter_mut().enumerate().for_each(|(target_index, acceleration)| {
+                *acceleration = self.barnes_hut_force_recursive(barnes_hut, target_index);
+            });
+            return accelerations;
         }
 
-        let s_over_d = tree.nodes[node_index].boundary.max_dimension() / sq_radius.sqrt();
-        if s_over_d < self.config.theta || tree.nodes[node_index].leaves.is_none() {
-            let force_magnitude = self.config.gravity * target_particle.mass * node_data.mass / sq_radius;
-            *acceleration += pointing.normalize() * force_magnitude / target_particle.mass;
-        }
-        else if let Some(leaf_start) = tree.nodes[node_index].leaves {
-            (leaf_start..(leaf_start + QuadTree::STEM_LEAF_COUNT)).for_each(|leaf_index| {
-                self.calculate_force_recursive(leaf_index, target_index, tree, barnes_hut, acceleration);
+        let chunk_size = self.particles.len().div_ceil(self.config.threads);
+        thread::scope(|scope| {
+            accelerations.chunks_mut(chunk_size).enumerate().for_each(|(chunk_index, chunk)| {
+                scope.spawn(move || {
+                    chunk.iter_mut().enumerate().for_each(|(offset, acceleration)| {
+                        let target_index = chunk_index * chunk_size + offset;
+                        *acceleration = self.barnes_hut_force_recursive(barnes_hut, target_index);
+                    });
+                });
             });
-        }
+        });
+
+        accelerations
     }
+
+    fn barnes_hut_force_recursive(&self, barnes_hut: &BarnesHutWrapper, target_index: usize) -> Vec2 {
+        barnes_hut.acceleration(&self.quadtree, &self.particles, target_index, self.config.force_parameters())
+    }
+}
+
+/// what works out the gravity in `State::update_barnes_hut`, both run on
+/// State->quadtree
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForceSolver {
+    // per particle tree walk, split across config.threads
+    BarnesHut,
+    // fast multipole method, one pass for every particle at once. O(N) but
+    // single threaded, so it pays off for the big runs
+    Fmm,
 }
 
 #[repr(C)]
@@ -235,4 +334,22 @@ pub struct SimulationConfig {
     pub mass_rand_max: f32,
     pub frame_time_dt_mod: f32,
     pub neighbor_distance: f32,
+    // bulk build the tree along a z-curve and keep the particles sorted the
+    // same way, so the force loops walk memory mostly in order
+    pub z_order_particles: bool,
+    // worker threads for the tree build and barnes-hut force pass, 1 is serial.
+    // the tree build ignores it for center splits with z_order_particles set
+    pub threads: usize,
+    // add quadrupole corrections to the barnes-hut nodes, see
+    // BarnesHutWrapper::with_quadrupole
+    pub quadrupole_moments: bool,
+    pub force_solver: ForceSolver,
+    // expansion order for ForceSolver::Fmm
+    pub fmm_order: usize,
+}
+
+impl SimulationConfig {
+    pub fn force_parameters(&self) -> ForceParameters {
+        ForceParameters { gravity: self.gravity, epsilon_squared: self.epsilon_squared, theta: self.theta }
+    }
 }
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::mem::size_of;
+
+use crate::quadtree::QuadTree;
+use crate::quadtree::QuadTreeNode;
+use crate::traversal::TreeTraversal;
+
+/// snapshot of how the tree is shaped, for tuning `leaf_capacity` and the
+/// depth limits
+#[repr(C)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeStats {
+    // deepest leaf, the root is depth 0
+    pub depth: usize,
+    // reachable nodes, detached groups waiting for reuse aren't counted
+    pub node_count: usize,
+    pub stem_count: usize,
+    pub leaf_count: usize,
+    pub empty_leaf_count: usize,
+    pub item_count: usize,
+    pub overflow_count: usize,
+    // occupancy_histogram[n] is how many leaves hold exactly n items
+    pub occupancy_histogram: Vec<usize>,
+    // leaves_per_depth[d] is how many leaves sit at depth d
+    pub leaves_per_depth: Vec<usize>,
+    // over the non-empty leaves
+    pub mean_items_per_leaf: f32,
+    pub max_items_per_leaf: usize,
+    // capacity of every buffer the tree owns, not just the used part
+    pub heap_bytes: usize,
+}
+
+impl QuadTree {
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats { overflow_count: self.overflow.len(), ..TreeStats::default() };
+        self.pre_order().for_each(|node| {
+            stats.node_count += 1;
+            if !node.is_leaf() {
+                stats.stem_count += 1;
+                return;
+            }
+
+            let item_count = node.items.len();
+            stats.leaf_count += 1;
+            stats.depth = stats.depth.max(node.depth);
+            stats.item_count += item_count;
+            stats.max_items_per_leaf = stats.max_items_per_leaf.max(item_count);
+            if item_count == 0 {
+                stats.empty_leaf_count += 1;
+            }
+            if stats.occupancy_histogram.len() <= item_count {
+                stats.occupancy_histogram.resize(item_count + 1, 0);
+            }
+            stats.occupancy_histogram[item_count] += 1;
+            if stats.leaves_per_depth.len() <= node.depth {
+                stats.leaves_per_depth.resize(node.depth + 1, 0);
+            }
+            stats.leaves_per_depth[node.depth] += 1;
+        });
+
+        let filled_leaves = stats.leaf_count - stats.empty_leaf_count;
+        if filled_leaves > 0 {
+            stats.mean_items_per_leaf = stats.item_count as f32 / filled_leaves as f32;
+        }
+        stats.heap_bytes = self.nodes.capacity() * size_of::<QuadTreeNode>()
+            + (self.item_indices.capacity() + self.overflow.capacity() + self.free_node_groups.capacity())
+                * size_of::<usize>()
+            + self.weights.capacity() * size_of::<f32>();
+
+        stats
+    }
+}
+
+/// one line, short enough to log every frame
+impl fmt::Display for TreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {} nodes {} (stems {} leaves {} empty {}) items {} overflow {} items/leaf mean {:.2} max {} \
+             heap {:.1} KiB occupancy {:?}",
+            self.depth,
+            self.node_count,
+            self.stem_count,
+            self.leaf_count,
+            self.empty_leaf_count,
+            self.item_count,
+            self.overflow_count,
+            self.mean_items_per_leaf,
+            self.max_items_per_leaf,
+            self.heap_bytes as f32 / 1024.,
+            self.occupancy_histogram,
+        )
+    }
+}
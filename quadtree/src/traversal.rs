@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::aggregate::TreeHierarchy;
+
+/// one node as handed out by the traversals, everything a walk usually needs
+/// without reaching into the tree's node layout
+#[derive(Debug, Clone)]
+pub struct NodeRef<'a, B> {
+    pub index: usize,
+    pub depth: usize,
+    pub boundary: B,
+    // items stored directly in the node, always empty for stems
+    pub items: &'a [usize],
+    // node indices of the children, `None` for leaves
+    pub children: Option<Range<usize>>,
+}
+
+impl<B> NodeRef<'_, B> {
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_none()
+    }
+}
+
+/// what a `Visitor` wants done after seeing a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    // go on into the node's children
+    Descend,
+    // skip everything below this node, but keep walking the rest
+    Prune,
+    // end the whole walk right away
+    Stop,
+}
+
+/// gets every node of a depth-first walk (children in order) and decides how
+/// the walk goes on from there. closures work as visitors too
+pub trait Visitor<B> {
+    fn visit(&mut self, node: &NodeRef<'_, B>) -> Visit;
+}
+
+impl<B, F> Visitor<B> for F
+where
+    F: FnMut(&NodeRef<'_, B>) -> Visit,
+{
+    fn visit(&mut self, node: &NodeRef<'_, B>) -> Visit {
+        self(node)
+    }
+}
+
+/// iterators and visitor walks for every `TreeHierarchy`
+pub trait TreeTraversal: TreeHierarchy + Sized {
+    fn node(&self, node_index: usize) -> NodeRef<'_, Self::Boundary> {
+        NodeRef {
+            index: node_index,
+            depth: self.node_depth(node_index),
+            boundary: self.node_boundary(node_index),
+            items: self.node_items(node_index),
+            children: self.node_children(node_index),
+        }
+    }
+
+    /// parents before their children
+    fn pre_order(&self) -> PreOrder<'_, Self> {
+        PreOrder { tree: self, stack: vec![0] }
+    }
+
+    /// children before their parents
+    fn post_order(&self) -> PostOrder<'_, Self> {
+        PostOrder { tree: self, stack: vec![(0, false)] }
+    }
+
+    /// level by level, shallowest first
+    fn breadth_first(&self) -> BreadthFirst<'_, Self> {
+        BreadthFirst { tree: self, queue: VecDeque::from([0]) }
+    }
+
+    fn walk<V>(&self, visitor: &mut V)
+    where
+        V: Visitor<Self::Boundary>,
+    {
+        self.walk_from(0, visitor);
+    }
+
+    /// same as `walk` but only over the subtree under `start`
+    fn walk_from<V>(&self, start: usize, visitor: &mut V)
+    where
+        V: Visitor<Self::Boundary>,
+    {
+        let mut stack = vec![start];
+        while let Some(node_index) = stack.pop() {
+            let node = self.node(node_index);
+            match visitor.visit(&node) {
+                Visit::Stop => return,
+                Visit::Prune => {}
+                Visit::Descend => stack.extend(node.children.into_iter().flatten().rev()),
+            }
+        }
+    }
+
+    /// `walk` for a plain closure, which doesn't need its argument type
+    /// spelled out this way
+    fn walk_with<F>(&self, start: usize, mut visit: F)
+    where
+        F: FnMut(&NodeRef<'_, Self::Boundary>) -> Visit,
+    {
+        self.walk_from(start, &mut visit);
+    }
+}
+
+impl<Tree> TreeTraversal for Tree where Tree: TreeHierarchy {}
+
+pub struct PreOrder<'a, Tree> {
+    tree: &'a Tree,
+    stack: Vec<usize>,
+}
+
+impl<'a, Tree> Iterator for PreOrder<'a, Tree>
+where
+    Tree: TreeHierarchy,
+{
+    type Item = NodeRef<'a, Tree::Boundary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.tree.node(self.stack.pop()?);
+        self.stack.extend(node.children.clone().into_iter().flatten().rev());
+        Some(node)
+    }
+}
+
+pub struct PostOrder<'a, Tree> {
+    tree: &'a Tree,
+    // second field is whether the node's children are already on the stack
+    stack: Vec<(usize, bool)>,
+}
+
+impl<'a, Tree> Iterator for PostOrder<'a, Tree>
+where
+    Tree: TreeHierarchy,
+{
+    type Item = NodeRef<'a, Tree::Boundary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node_index, expanded)) = self.stack.pop() {
+            let node = self.tree.node(node_index);
+            match node.children.clone() {
+                Some(children) if !expanded => {
+                    self.stack.push((node_index, true));
+                    self.stack.extend(children.rev().map(|child| (child, false)));
+                }
+                _ => return Some(node),
+            }
+        }
+
+        None
+    }
+}
+
+pub struct BreadthFirst<'a, Tree> {
+    tree: &'a Tree,
+    queue: VecDeque<usize>,
+}
+
+impl<'a, Tree> Iterator for BreadthFirst<'a, Tree>
+where
+    Tree: TreeHierarchy,
+{
+    type Item = NodeRef<'a, Tree::Boundary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.tree.node(self.queue.pop_front()?);
+        self.queue.extend(node.children.clone().into_iter().flatten());
+        Some(node)
+    }
+}
@@ -1,6 +1,5 @@
 use glam::Vec2;
-
-use sokol::time;
+use glam::Vec3;
 
 pub const EPSILON: f32 = 1e-9;
 
@@ -32,6 +31,13 @@ impl BoundingBox {
         self.min.x <= point.x && self.min.y <= point.y && self.max.x > point.x && self.max.y > point.y
     }
 
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+    }
+
     pub fn overlaps(&self, other: &BoundingBox) -> bool {
         self.max.x > other.min.x
             && self.min.x <= other.max.x
@@ -39,12 +45,110 @@ impl BoundingBox {
             && self.min.y <= other.max.y
     }
 
+    /// squared distance from the point to the closest point of the box, zero
+    /// if the point is inside
+    pub fn distance_squared(&self, point: Vec2) -> f32 {
+        (point - point.clamp(self.min, self.max)).length_squared()
+    }
+
+    /// squared distance between the closest points of the two boxes, zero if
+    /// they overlap
+    pub fn distance_squared_to_box(&self, other: &BoundingBox) -> f32 {
+        let gap = (self.min - other.max).max(other.min - self.max).max(Vec2::ZERO);
+        gap.length_squared()
+    }
+
+    /// how far along the ray it first touches the box (0 when it starts
+    /// inside), `None` if it misses or only gets there after `max_t`.
+    /// `inverse_direction` is `1. / direction`, infinities are fine
+    pub fn ray_entry(&self, origin: Vec2, inverse_direction: Vec2, max_t: f32) -> Option<f32> {
+        let near = (self.min - origin) * inverse_direction;
+        let far = (self.max - origin) * inverse_direction;
+        // nan shows up for axis aligned rays sitting exactly on an edge
+        let t_min = near.min(far).max_element().max(0.);
+        let t_max = near.max(far).min_element().min(max_t);
+
+        (t_min <= t_max).then_some(t_min)
+    }
+
     pub fn center(&self) -> Vec2 {
         (self.min + self.max) / 2.
     }
 
+    /// the shortest version of `offset` when this box is a torus (its edges
+    /// wrap around), so each axis ends up within half a width/height
+    pub fn wrap_offset(&self, offset: Vec2) -> Vec2 {
+        let size = self.max - self.min;
+        offset - size * (offset / size).round()
+    }
+
+    /// moves the point by whole widths/heights until it is inside this box
+    pub fn wrap_point(&self, point: Vec2) -> Vec2 {
+        self.min + (point - self.min).rem_euclid(self.max - self.min)
+    }
+
+    /// `distance_squared` to whichever copy of the point is closest, with
+    /// `domain` wrapping around. the box has to fit inside `domain`
+    pub fn wrapped_distance_squared(&self, point: Vec2, domain: &BoundingBox) -> f32 {
+        let half_size = (self.max - self.min) / 2.;
+        let offset = domain.wrap_offset(point - self.center()).abs();
+        (offset - half_size).max(Vec2::ZERO).length_squared()
+    }
+
+    /// this box cut up along the edges of `domain` with the pieces hanging
+    /// over them moved to the opposite side, so overlap and contains checks
+    /// against all of them act like `domain` wraps around. up to 4 boxes
+    pub fn wrapped_images(&self, domain: &BoundingBox) -> Vec<BoundingBox> {
+        let size = domain.max - domain.min;
+        // once the box starts inside the domain it can only hang over the max
+        // edges, anything at least as wide as the domain covers all of it
+        let start = domain.wrap_point(self.min);
+        let wrapped = |axis: usize| -> Vec<(f32, f32)> {
+            if self.max[axis] - self.min[axis] >= size[axis] {
+                return vec![(domain.min[axis], domain.max[axis])];
+            }
+
+            let end = start[axis] + self.max[axis] - self.min[axis];
+            if end > domain.max[axis] {
+                vec![(start[axis], domain.max[axis]), (domain.min[axis], end - size[axis])]
+            }
+            else {
+                vec![(start[axis], end)]
+            }
+        };
+
+        let rows = wrapped(1);
+        wrapped(0)
+            .into_iter()
+            .flat_map(|(min_x, max_x)| {
+                rows.iter().map(move |&(min_y, max_y)| {
+                    BoundingBox::build(Vec2::new(min_x, min_y), Vec2::new(max_x, max_y))
+                })
+            })
+            .collect()
+    }
+
+    /// which of the `split_quadrants` boxes the point falls in, without
+    /// building them. the point is assumed to be inside this box
+    pub fn quadrant_of(&self, point: Vec2) -> usize {
+        Self::quadrant_around(self.center(), point)
+    }
+
+    /// same as `quadrant_of` for boxes split at `split` instead of the center
+    pub fn quadrant_around(split: Vec2, point: Vec2) -> usize {
+        match (point.x >= split.x, point.y >= split.y) {
+            (true, false) => 0,  // i
+            (false, false) => 1, // ii
+            (false, true) => 2,  // iii
+            (true, true) => 3,   // iv
+        }
+    }
+
     pub fn split_quadrants(&self) -> [Self; 4] {
-        let center = self.center();
+        self.split_quadrants_at(self.center())
+    }
+
+    pub fn split_quadrants_at(&self, center: Vec2) -> [Self; 4] {
         /* follows the unit circle quadrant conventions, but the origin is in
         the top left so it is a little confusing */
         [
@@ -56,18 +160,56 @@ impl BoundingBox {
     }
 }
 
-#[derive(Debug)]
-pub struct Clock {
-    pub curr_time: u64,
-    pub last_time: u64,
-    pub frame_time: f32,
+/// 3D version of `BoundingBox`, split into octants instead of quadrants
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox3 {
+    pub min: Vec3,
+    pub max: Vec3,
 }
 
-impl Clock {
-    pub fn update(&mut self, now: u64) {
-        self.last_time = self.curr_time;
-        self.curr_time = now;
-        self.frame_time = time::sec(self.curr_time - self.last_time) as f32;
+impl BoundingBox3 {
+    pub fn build(min: Vec3, max: Vec3) -> Self {
+        BoundingBox3 { min, max }
+    }
+
+    pub fn max_dimension(&self) -> f32 {
+        (self.max - self.min).max_element()
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.min.cmple(point).all() && self.max.cmpgt(point).all()
+    }
+
+    pub fn overlaps(&self, other: &BoundingBox3) -> bool {
+        self.max.cmpgt(other.min).all() && self.min.cmple(other.max).all()
+    }
+
+    pub fn distance_squared(&self, point: Vec3) -> f32 {
+        (point - point.clamp(self.min, self.max)).length_squared()
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.
+    }
+
+    /// which of the `split_octants` boxes the point falls in. bit 0 is +x,
+    /// bit 1 is +y and bit 2 is +z
+    pub fn octant_of(&self, point: Vec3) -> usize {
+        let center = self.center();
+        (point.x >= center.x) as usize
+            | ((point.y >= center.y) as usize) << 1
+            | ((point.z >= center.z) as usize) << 2
+    }
+
+    pub fn split_octants(&self) -> [Self; 8] {
+        let center = self.center();
+        std::array::from_fn(|octant| {
+            let upper = Vec3::new((octant & 1) as f32, ((octant >> 1) & 1) as f32, (octant >> 2) as f32);
+            let min = self.min + (center - self.min) * upper;
+            let max = center + (self.max - center) * upper;
+            BoundingBox3::build(min, max)
+        })
     }
 }
 
@@ -91,7 +233,3 @@ pub fn zero_centered_range_vec2(scale: f32) -> Vec2 {
 pub fn mouse_to_screen(mousex: f32, mousey: f32, dimensions: &BoundingBox) -> Vec2 {
     Vec2::new(mousex, dimensions.height() - mousey)
 }
-
-pub fn wait(time_ms: u64) {
-    std::thread::sleep(std::time::Duration::from_millis(time_ms));
-}
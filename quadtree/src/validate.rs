@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+
+use glam::Vec2;
+
+use crate::quadtree::DuplicatePolicy;
+use crate::quadtree::OutOfBoundsPolicy;
+use crate::quadtree::PositionPlanar;
+use crate::quadtree::QuadTree;
+
+/// one broken invariant found by `QuadTree::validate`, node and item fields
+/// are indices into QuadTree->nodes and the items slice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeViolation {
+    // nodes should be the root plus whole groups of 4
+    NodeCount { len: usize },
+    // a stem's leaves run past the end of QuadTree->nodes
+    LeavesOutOfRange { stem: usize, leaf_start: usize },
+    // reached from two different stems, or from itself
+    NodeReachedTwice { node: usize },
+    // the child isn't exactly its quadrant of the parent's boundary
+    ChildBoundary { stem: usize, child: usize },
+    ChildDepth { stem: usize, child: usize },
+    // stems keep their items in their leaves, never directly
+    StemHoldsItems { stem: usize },
+    // data_head/data_len run past the end of QuadTree->item_indices
+    ItemRunOutOfRange { leaf: usize },
+    // the stored index doesn't point into the items slice
+    ItemIndexOutOfRange { item: usize },
+    ItemOutsideLeaf { leaf: usize, item: usize },
+    // overflow is only for items outside of the root
+    OverflowInsideRoot { item: usize },
+    DuplicateItem { item: usize },
+    // only checked by `validate_stored`
+    MissingItem { item: usize },
+    UnexpectedItem { item: usize },
+}
+
+impl fmt::Display for TreeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeViolation::NodeCount { len } => write!(f, "{len} nodes isn't the root plus groups of 4"),
+            TreeViolation::LeavesOutOfRange { stem, leaf_start } => {
+                write!(f, "stem {stem} points at leaves {leaf_start}.. past the last node")
+            }
+            TreeViolation::NodeReachedTwice { node } => write!(f, "node {node} is reachable more than once"),
+            TreeViolation::ChildBoundary { stem, child } => {
+                write!(f, "child {child} doesn't tile its quadrant of stem {stem}")
+            }
+            TreeViolation::ChildDepth { stem, child } => {
+                write!(f, "child {child} isn't one level deeper than stem {stem}")
+            }
+            TreeViolation::StemHoldsItems { stem } => write!(f, "stem {stem} still has data_head set"),
+            TreeViolation::ItemRunOutOfRange { leaf } => {
+                write!(f, "leaf {leaf} points past the end of item_indices")
+            }
+            TreeViolation::ItemIndexOutOfRange { item } => {
+                write!(f, "item {item} is past the end of the items")
+            }
+            TreeViolation::ItemOutsideLeaf { leaf, item } => {
+                write!(f, "item {item} is stored in leaf {leaf} which doesn't contain it")
+            }
+            TreeViolation::OverflowInsideRoot { item } => {
+                write!(f, "item {item} is in overflow but inside the root")
+            }
+            TreeViolation::DuplicateItem { item } => write!(f, "item {item} is stored more than once"),
+            TreeViolation::MissingItem { item } => write!(f, "item {item} isn't stored anywhere"),
+            TreeViolation::UnexpectedItem { item } => {
+                write!(f, "item {item} is stored but shouldn't be in the tree")
+            }
+        }
+    }
+}
+
+/// everything `QuadTree::validate` or `QuadTree::validate_stored` found wrong with the tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub violations: Vec<TreeViolation>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} quadtree invariants are broken", self.violations.len())?;
+        self.violations.iter().try_for_each(|violation| write!(f, "\n  {violation}"))
+    }
+}
+
+impl Error for ValidationError {}
+
+impl QuadTree {
+    /// walks the whole tree and checks it against `items`, which have to be
+    /// where they were when the tree was last built or updated. doesn't care
+    /// which items are stored, since removed ones are legitimately left out,
+    /// see `validate_stored` for that
+    pub fn validate<T>(&self, items: &[T]) -> Result<(), ValidationError>
+    where
+        T: PositionPlanar,
+    {
+        let (violations, _) = self.check_structure(items);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(ValidationError { violations })
+    }
+
+    /// `validate`, and also that exactly the `expected` items are stored, e.g.
+    /// every index after `construct_tree` or all but the removed ones after
+    /// `remove`. out of bounds items only count as missing under
+    /// `OutOfBoundsPolicy::Overflow`, and items sharing a position with a
+    /// stored one don't under `DuplicatePolicy::Discard`
+    pub fn validate_stored<T>(&self, items: &[T], expected: &[usize]) -> Result<(), ValidationError>
+    where
+        T: PositionPlanar,
+    {
+        let (mut violations, seen) = self.check_structure(items);
+        let mut is_expected = vec![false; items.len()];
+        expected.iter().for_each(|&item_index| {
+            if item_index < items.len() {
+                is_expected[item_index] = true;
+            }
+            else {
+                violations.push(TreeViolation::ItemIndexOutOfRange { item: item_index });
+            }
+        });
+        (0..items.len()).filter(|&item_index| seen[item_index] > 0 && !is_expected[item_index]).for_each(
+            |item_index| {
+                violations.push(TreeViolation::UnexpectedItem { item: item_index });
+            },
+        );
+
+        // positions that made it in, for telling discarded duplicates apart
+        // from items that actually went missing
+        let stored_positions: HashSet<(u32, u32)> = match self.duplicate_policy {
+            DuplicatePolicy::Discard => (0..items.len())
+                .filter(|&item_index| seen[item_index] > 0)
+                .map(|item_index| {
+                    let position = items[item_index].position();
+                    (position.x.to_bits(), position.y.to_bits())
+                })
+                .collect(),
+            DuplicatePolicy::Keep => HashSet::new(),
+        };
+        let root = self.nodes[Self::ROOT_INDEX].boundary;
+        (0..items.len()).filter(|&item_index| is_expected[item_index] && seen[item_index] == 0).for_each(
+            |item_index| {
+                let position = items[item_index].position();
+                let missing = if root.contains(position) {
+                    !stored_positions.contains(&(position.x.to_bits(), position.y.to_bits()))
+                }
+                else {
+                    self.out_of_bounds_policy == OutOfBoundsPolicy::Overflow && position.is_finite()
+                };
+                if missing {
+                    violations.push(TreeViolation::MissingItem { item: item_index });
+                }
+            },
+        );
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(ValidationError { violations })
+    }
+
+    /// everything but which items are stored, along with how many times each
+    /// item was found
+    fn check_structure<T>(&self, items: &[T]) -> (Vec<TreeViolation>, Vec<usize>)
+    where
+        T: PositionPlanar,
+    {
+        let mut violations = Vec::new();
+        if self.nodes.len() % Self::STEM_LEAF_COUNT != 1 {
+            violations.push(TreeViolation::NodeCount { len: self.nodes.len() });
+        }
+
+        let mut seen = vec![0_usize; items.len()];
+        let mut count_item = |item_index: usize, violations: &mut Vec<TreeViolation>| {
+            if item_index >= items.len() {
+                violations.push(TreeViolation::ItemIndexOutOfRange { item: item_index });
+                return false;
+            }
+            seen[item_index] += 1;
+            if seen[item_index] == 2 {
+                violations.push(TreeViolation::DuplicateItem { item: item_index });
+            }
+            true
+        };
+
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![Self::ROOT_INDEX];
+        while let Some(node_index) = stack.pop() {
+            if visited[node_index] {
+                violations.push(TreeViolation::NodeReachedTwice { node: node_index });
+                continue;
+            }
+            visited[node_index] = true;
+            let node = &self.nodes[node_index];
+
+            if let Some(leaf_start) = node.leaves {
+                if node.data_head.is_some() || node.data_len > 0 {
+                    violations.push(TreeViolation::StemHoldsItems { stem: node_index });
+                }
+                if leaf_start + Self::STEM_LEAF_COUNT > self.nodes.len() {
+                    violations.push(TreeViolation::LeavesOutOfRange { stem: node_index, leaf_start });
+                    continue;
+                }
+
+                // children can meet anywhere inside the parent, depending on
+                // the split policy
+                let first = self.nodes[leaf_start].boundary;
+                let split = Vec2::new(first.min.x, first.max.y);
+                if !(node.boundary.min.cmple(split).all() && split.cmple(node.boundary.max).all()) {
+                    violations.push(TreeViolation::ChildBoundary { stem: node_index, child: leaf_start });
+                }
+                node.boundary.split_quadrants_at(split).iter().enumerate().for_each(
+                    |(quadrant, expected)| {
+                        let child = &self.nodes[leaf_start + quadrant];
+                        if child.boundary.min != expected.min || child.boundary.max != expected.max {
+                            violations.push(TreeViolation::ChildBoundary {
+                                stem: node_index,
+                                child: leaf_start + quadrant,
+                            });
+                        }
+                        if child.depth != node.depth + 1 {
+                            violations.push(TreeViolation::ChildDepth {
+                                stem: node_index,
+                                child: leaf_start + quadrant,
+                            });
+                        }
+                    },
+                );
+                stack.extend((leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).rev());
+                continue;
+            }
+
+            if node.data_head.is_some_and(|data_head| data_head + node.data_len > self.item_indices.len()) {
+                violations.push(TreeViolation::ItemRunOutOfRange { leaf: node_index });
+                continue;
+            }
+            self.leaf_items(node_index).iter().for_each(|&item_index| {
+                if count_item(item_index, &mut violations)
+                    && !node.boundary.contains(items[item_index].position())
+                {
+                    violations.push(TreeViolation::ItemOutsideLeaf { leaf: node_index, item: item_index });
+                }
+            });
+        }
+
+        let root = self.nodes[Self::ROOT_INDEX].boundary;
+        self.overflow.iter().for_each(|&item_index| {
+            if count_item(item_index, &mut violations) && root.contains(items[item_index].position()) {
+                violations.push(TreeViolation::OverflowInsideRoot { item: item_index });
+            }
+        });
+
+        (violations, seen)
+    }
+
+    /// Graphviz DOT of the node hierarchy, one box per reachable node with its
+    /// boundary and how many items it holds
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph quadtree {\n    node [shape=box, fontname=monospace];\n");
+        self.for_each_reachable(|node_index, _| {
+            let node = &self.nodes[node_index];
+            let _ = writeln!(
+                dot,
+                "    n{node_index} [label=\"{node_index} depth {}\\n({}, {}) .. ({}, {})\\n{} items\"];",
+                node.depth,
+                node.boundary.min.x,
+                node.boundary.min.y,
+                node.boundary.max.x,
+                node.boundary.max.y,
+                node.data_len,
+            );
+            if let Some(leaf_start) = node.leaves {
+                (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).for_each(|leaf| {
+                    let _ = writeln!(dot, "    n{node_index} -> n{leaf};");
+                });
+            }
+        });
+        if !self.overflow.is_empty() {
+            let _ = writeln!(dot, "    overflow [label=\"overflow\\n{} items\"];", self.overflow.len());
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// indented text version of `to_dot`, leaves also list their items
+    pub fn dump_text(&self) -> String {
+        let mut text = String::new();
+        self.for_each_reachable(|node_index, depth| {
+            let node = &self.nodes[node_index];
+            let _ = write!(
+                text,
+                "{:indent$}{node_index} ({}, {}) .. ({}, {})",
+                "",
+                node.boundary.min.x,
+                node.boundary.min.y,
+                node.boundary.max.x,
+                node.boundary.max.y,
+                indent = depth * 2,
+            );
+            match node.leaves {
+                Some(_) => text.push_str(" stem\n"),
+                None => {
+                    let _ = writeln!(text, " leaf {:?}", self.leaf_items(node_index));
+                }
+            }
+        });
+        let _ = writeln!(text, "overflow {:?}", self.overflow);
+
+        text
+    }
+
+    /// pre-order walk over the nodes reachable from the root, with how far
+    /// down each one is. stops at anything out of range so it is safe to call
+    /// on a broken tree
+    fn for_each_reachable<F>(&self, mut callback: F)
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![(Self::ROOT_INDEX, 0)];
+        while let Some((node_index, depth)) = stack.pop() {
+            if node_index >= self.nodes.len() || visited[node_index] {
+                continue;
+            }
+            visited[node_index] = true;
+            callback(node_index, depth);
+            if let Some(leaf_start) = self.nodes[node_index].leaves {
+                stack.extend(
+                    (leaf_start..(leaf_start + Self::STEM_LEAF_COUNT)).rev().map(|leaf| (leaf, depth + 1)),
+                );
+            }
+        }
+    }
+}
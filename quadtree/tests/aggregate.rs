@@ -0,0 +1,92 @@
+use glam::Vec2;
+use quadtree::aggregate::aggregate;
+use quadtree::aggregate::aggregate_into;
+use quadtree::BoundingBox;
+use quadtree::ContentBounds;
+use quadtree::ItemCount;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+use quadtree::TreeTraversal;
+
+mod common;
+
+use common::random_points;
+
+/// checks the count and bounds of every node reachable from the root against
+/// the present points inside its boundary
+fn assert_matches_brute_force(tree: &QuadTree, points: &[Vec2], present: &[bool]) {
+    let counts: Vec<ItemCount> = aggregate(tree, points);
+    let bounds: Vec<ContentBounds> = aggregate(tree, points);
+    tree.pre_order().for_each(|node| {
+        let inside: Vec<Vec2> = (0..points.len())
+            .filter(|&index| present[index] && node.boundary.contains(points[index]))
+            .map(|index| points[index])
+            .collect();
+        assert_eq!(counts[node.index], ItemCount(inside.len()), "node {}", node.index);
+
+        let Some(found) = bounds[node.index].0
+        else {
+            assert!(inside.is_empty(), "node {}", node.index);
+            return;
+        };
+        let min = inside.iter().fold(Vec2::splat(f32::INFINITY), |min, point| min.min(*point));
+        let max = inside.iter().fold(Vec2::splat(f32::NEG_INFINITY), |max, point| max.max(*point));
+        assert_eq!(found.min, min, "node {}", node.index);
+        assert_eq!(found.max, Vec2::new(max.x.next_up(), max.y.next_up()), "node {}", node.index);
+        assert!(inside.iter().all(|&point| found.contains(point)), "node {}", node.index);
+    });
+}
+
+#[test]
+fn aggregates_match_brute_force() {
+    fastrand::seed(211);
+    let mut points = random_points(2000, Vec2::ZERO, Vec2::splat(1000.));
+    points.extend(random_points(500, Vec2::splat(300.), Vec2::splat(320.)));
+    let present = vec![true; points.len()];
+    [SplitPolicy::Center, SplitPolicy::Median].into_iter().for_each(|split_policy| {
+        let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+            .with_split_policy(split_policy);
+        tree.construct_tree(&points);
+        assert_matches_brute_force(&tree, &points, &present);
+
+        let counts: Vec<ItemCount> = aggregate(&tree, &points);
+        assert_eq!(counts[QuadTree::ROOT_INDEX], ItemCount(points.len()));
+    });
+}
+
+#[test]
+fn aggregates_after_collapsing_removes() {
+    fastrand::seed(212);
+    let points = random_points(1500, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+    let mut counts: Vec<ItemCount> = aggregate(&tree, &points);
+
+    // emptying most of the lower half collapses its stems, leaving their node
+    // groups detached for reuse
+    let mut present = vec![true; points.len()];
+    (0..points.len()).filter(|&index| points[index].y < 45. && index % 10 != 0).for_each(|index| {
+        assert!(tree.remove(index, &points));
+        present[index] = false;
+    });
+    assert!(!tree.free_node_groups.is_empty());
+    assert_matches_brute_force(&tree, &points, &present);
+
+    // reusing the old allocation gives the same values for every reachable
+    // node, the detached ones are left at whatever they were
+    aggregate_into(&tree, &points, &mut counts);
+    let fresh: Vec<ItemCount> = aggregate(&tree, &points);
+    tree.pre_order().for_each(|node| assert_eq!(counts[node.index], fresh[node.index]));
+    assert_eq!(counts[QuadTree::ROOT_INDEX], ItemCount(present.iter().filter(|&&present| present).count()));
+}
+
+#[test]
+fn empty_tree_aggregates() {
+    let points: Vec<Vec2> = Vec::new();
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+    let counts: Vec<ItemCount> = aggregate(&tree, &points);
+    let bounds: Vec<ContentBounds> = aggregate(&tree, &points);
+    assert_eq!(counts, vec![ItemCount(0)]);
+    assert!(bounds[QuadTree::ROOT_INDEX].0.is_none());
+}
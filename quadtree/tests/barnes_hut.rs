@@ -0,0 +1,63 @@
+use glam::Vec2;
+use quadtree::BarnesHutWrapper;
+use quadtree::BoundingBox;
+use quadtree::ForceParameters;
+use quadtree::Particle;
+use quadtree::QuadTree;
+
+mod common;
+
+use common::direct_summation;
+use common::random_particles;
+use common::relative_errors;
+use common::PARAMETERS;
+
+fn accelerations(
+    tree: &QuadTree, particles: &[Particle], parameters: ForceParameters, use_quadrupole: bool,
+) -> Vec<Vec2> {
+    let mut barnes_hut = BarnesHutWrapper::new().with_quadrupole(use_quadrupole);
+    barnes_hut.build_hierarchy(tree, particles);
+    (0..particles.len())
+        .map(|target_index| barnes_hut.acceleration(tree, particles, target_index, parameters))
+        .collect()
+}
+
+#[test]
+fn quadrupoles_beat_monopoles() {
+    fastrand::seed(241);
+    // a dense clump on top of an even spread, so the moments are far from 0
+    let mut particles = random_particles(2000, Vec2::ZERO, Vec2::new(1920., 1080.));
+    particles.extend(random_particles(1000, Vec2::new(1200., 300.), Vec2::new(1350., 420.)));
+    let mut tree = QuadTree::build(3, BoundingBox::build(Vec2::ZERO, Vec2::new(1920., 1080.)));
+    tree.construct_tree(&particles);
+
+    let exact = direct_summation(&particles, PARAMETERS);
+    [0.5, 0.7, 1.].into_iter().for_each(|theta| {
+        let parameters = ForceParameters { theta, ..PARAMETERS };
+        let monopole = relative_errors(&accelerations(&tree, &particles, parameters, false), &exact).0;
+        let quadrupole = relative_errors(&accelerations(&tree, &particles, parameters, true), &exact).0;
+        assert!(quadrupole < monopole / 2., "theta {theta}: quadrupole {quadrupole} vs monopole {monopole}");
+    });
+}
+
+#[test]
+fn monopole_walk_is_the_plain_barnes_hut_sum() {
+    // a far pair seen as one mass and no force from the target's own leaf,
+    // which only ever holds the target itself here
+    let particles = vec![
+        Particle::new(Vec2::new(100., 100.), Vec2::ZERO, 1.),
+        Particle::new(Vec2::new(900., 900.), Vec2::ZERO, 2.),
+        Particle::new(Vec2::new(910., 900.), Vec2::ZERO, 2.),
+    ];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    tree.construct_tree(&particles);
+    let mut barnes_hut = BarnesHutWrapper::new();
+    barnes_hut.build_hierarchy(&tree, &particles);
+    assert!(barnes_hut.quadrupoles.is_empty());
+
+    let parameters = ForceParameters { theta: 1., ..PARAMETERS };
+    let found = barnes_hut.acceleration(&tree, &particles, 0, parameters);
+    let pointing = Vec2::new(905., 900.) - particles[0].position;
+    let expected = pointing.normalize() * PARAMETERS.gravity * 4. / pointing.length_squared();
+    assert!((found - expected).length() < 1e-6 * expected.length(), "{found} vs {expected}");
+}
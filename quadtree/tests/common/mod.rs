@@ -0,0 +1,86 @@
+//! fixtures shared by the integration tests, each test file only uses some of
+//! them
+#![allow(dead_code)]
+
+use glam::Vec2;
+use glam::Vec3;
+use quadtree::BoundingBox;
+use quadtree::ForceParameters;
+use quadtree::Particle;
+
+pub const PARAMETERS: ForceParameters = ForceParameters { gravity: 1e2, epsilon_squared: 10., theta: 0.7 };
+
+pub fn random_points(count: usize, min: Vec2, max: Vec2) -> Vec<Vec2> {
+    (0..count).map(|_| min + Vec2::new(fastrand::f32(), fastrand::f32()) * (max - min)).collect()
+}
+
+pub fn random_points_3d(count: usize, min: Vec3, max: Vec3) -> Vec<Vec3> {
+    (0..count)
+        .map(|_| min + Vec3::new(fastrand::f32(), fastrand::f32(), fastrand::f32()) * (max - min))
+        .collect()
+}
+
+/// at rest, with masses between 1 and 101
+pub fn random_particles(count: usize, min: Vec2, max: Vec2) -> Vec<Particle> {
+    (0..count)
+        .map(|_| {
+            let position = min + Vec2::new(fastrand::f32(), fastrand::f32()) * (max - min);
+            Particle::new(position, Vec2::ZERO, fastrand::f32() * 100. + 1.)
+        })
+        .collect()
+}
+
+pub fn sorted(mut found: Vec<usize>) -> Vec<usize> {
+    found.sort_unstable();
+    found
+}
+
+pub fn brute_force_range(points: &[Vec2], boundary: &BoundingBox) -> Vec<usize> {
+    (0..points.len()).filter(|&index| boundary.contains(points[index])).collect()
+}
+
+pub fn brute_force_radius(points: &[Vec2], center: Vec2, radius: f32) -> Vec<usize> {
+    (0..points.len()).filter(|&index| points[index].distance_squared(center) <= radius * radius).collect()
+}
+
+/// (index, distance) of the `count` closest points, closest first and ties by
+/// index
+pub fn brute_force_nearest(points: &[Vec2], point: Vec2, count: usize) -> Vec<(usize, f32)> {
+    let mut sorted: Vec<(usize, f32)> =
+        (0..points.len()).map(|index| (index, points[index].distance_squared(point))).collect();
+    sorted.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    sorted.truncate(count);
+
+    sorted.into_iter().map(|(index, distance_squared)| (index, distance_squared.sqrt())).collect()
+}
+
+/// the exact pull on every particle, with the same epsilon cutoff the solvers
+/// use
+pub fn direct_summation(particles: &[Particle], parameters: ForceParameters) -> Vec<Vec2> {
+    (0..particles.len())
+        .map(|target_index| {
+            let target = &particles[target_index];
+            particles.iter().enumerate().fold(Vec2::ZERO, |acceleration, (other_index, other)| {
+                let pointing = other.position - target.position;
+                let sq_radius = pointing.length_squared();
+                if other_index == target_index || sq_radius < parameters.epsilon_squared {
+                    return acceleration;
+                }
+
+                acceleration + pointing.normalize() * parameters.gravity * other.mass / sq_radius
+            })
+        })
+        .collect()
+}
+
+/// (median, max) of |approximate - exact| / |exact|
+pub fn relative_errors(approximate: &[Vec2], exact: &[Vec2]) -> (f32, f32) {
+    let mut errors: Vec<f32> = approximate
+        .iter()
+        .zip(exact)
+        .map(|(approximate, exact)| (*approximate - *exact).length() / exact.length())
+        .collect();
+    errors.sort_by(f32::total_cmp);
+
+    (errors[errors.len() / 2], errors[errors.len() - 1])
+}
@@ -0,0 +1,371 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::DuplicatePolicy;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::Particle;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+
+mod common;
+
+use common::random_points;
+
+/// every item index stored in a leaf, sorted
+fn stored_items(tree: &QuadTree) -> Vec<usize> {
+    let mut stored: Vec<usize> =
+        (0..tree.nodes.len()).flat_map(|node_index| tree.leaf_items(node_index).iter().copied()).collect();
+    stored.sort_unstable();
+
+    stored
+}
+
+/// (min, max, items) of every leaf that holds something, sorted so trees with
+/// a different node layout compare equal
+fn leaf_contents(tree: &QuadTree) -> Vec<([u32; 4], Vec<usize>)> {
+    let mut leaves: Vec<([u32; 4], Vec<usize>)> = (0..tree.nodes.len())
+        .filter(|&node_index| tree.nodes[node_index].data_len > 0)
+        .map(|node_index| {
+            let boundary = tree.nodes[node_index].boundary;
+            let mut items = tree.leaf_items(node_index).to_vec();
+            items.sort_unstable();
+            let corners = [boundary.min.x, boundary.min.y, boundary.max.x, boundary.max.y].map(f32::to_bits);
+            (corners, items)
+        })
+        .collect();
+    leaves.sort_unstable();
+
+    leaves
+}
+
+/// every query kind over a spread of boxes and points, with results sorted
+fn query_results(tree: &QuadTree, points: &[Vec2]) -> Vec<Vec<usize>> {
+    fastrand::seed(99);
+    (0..100)
+        .flat_map(|_| {
+            let corners = random_points(2, Vec2::splat(-50.), Vec2::splat(1050.));
+            let boundary = BoundingBox::build(corners[0].min(corners[1]), corners[0].max(corners[1]));
+            let center = random_points(1, Vec2::splat(-50.), Vec2::splat(1050.))[0];
+            let mut range = tree.query_range_exact(&boundary, points);
+            let mut radius = tree.query_radius(center, fastrand::f32() * 200., points);
+            let nearest = tree.nearest(center, 10, points).into_iter().map(|(index, _)| index).collect();
+            range.sort_unstable();
+            radius.sort_unstable();
+            [range, radius, nearest]
+        })
+        .collect()
+}
+
+#[test]
+fn max_depth_stops_splitting() {
+    fastrand::seed(61);
+    let points = random_points(2000, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.))).with_limits(3, 0.);
+    tree.construct_tree(&points);
+
+    assert!(tree.nodes.iter().all(|node| node.depth <= 3));
+    assert!(tree.nodes.iter().filter(|node| node.depth == 3).all(|node| node.leaves.is_none()));
+    // the deepest leaves go over capacity instead
+    assert!(tree.nodes.iter().any(|node| node.data_len > tree.leaf_capacity));
+    assert_eq!(stored_items(&tree), (0..points.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn min_cell_size_stops_splitting() {
+    fastrand::seed(62);
+    let points = random_points(2000, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_limits(QuadTree::DEFAULT_MAX_DEPTH, 10.);
+    tree.construct_tree(&points);
+
+    assert!(tree
+        .nodes
+        .iter()
+        .filter(|node| node.leaves.is_some())
+        .all(|node| node.boundary.max_dimension() / 2. >= 10.));
+    assert_eq!(stored_items(&tree), (0..points.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn coincident_points_share_one_leaf() {
+    fastrand::seed(63);
+    let mut points = vec![Vec2::splat(25.); 100];
+    points.extend(random_points(100, Vec2::ZERO, Vec2::splat(100.)));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    // splitting could never separate them, so they stay over capacity in one
+    // leaf instead of running down to max_depth
+    let leaf = tree.find_leaf(Vec2::splat(25.)).unwrap();
+    assert!(tree.nodes[leaf].depth < QuadTree::DEFAULT_MAX_DEPTH);
+    let coincident: Vec<usize> =
+        tree.leaf_items(leaf).iter().copied().filter(|&item_index| item_index < 100).collect();
+    assert_eq!(coincident.len(), 100);
+    assert_eq!(stored_items(&tree), (0..points.len()).collect::<Vec<_>>());
+
+    let mut found = tree.query_range_exact(&BoundingBox::build(Vec2::splat(24.), Vec2::splat(26.)), &points);
+    found.sort_unstable();
+    let expected: Vec<usize> = (0..points.len())
+        .filter(|&index| BoundingBox::build(Vec2::splat(24.), Vec2::splat(26.)).contains(points[index]))
+        .collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn discard_keeps_the_first_item_at_each_position() {
+    fastrand::seed(64);
+    let positions = random_points(50, Vec2::ZERO, Vec2::splat(100.));
+    let points: Vec<Vec2> = (0..400).map(|_| positions[fastrand::usize(..positions.len())]).collect();
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_duplicate_policy(DuplicatePolicy::Discard);
+    tree.construct_tree(&points);
+
+    let expected: Vec<usize> =
+        (0..points.len()).filter(|&index| !points[..index].contains(&points[index])).collect();
+    assert_eq!(stored_items(&tree), expected);
+
+    // inserting another copy of a stored position is dropped as well
+    let mut points = points;
+    points.push(points[expected[0]]);
+    tree.insert(points.len() - 1, &points).unwrap();
+    assert_eq!(stored_items(&tree), expected);
+}
+
+#[test]
+fn discard_treats_negative_zero_as_zero() {
+    let points = vec![
+        Vec2::ZERO,
+        Vec2::new(-0., 0.),
+        Vec2::new(0., -0.),
+        Vec2::splat(-0.),
+        Vec2::ONE,
+        Vec2::new(-0., 1.),
+        Vec2::new(0., 1.),
+    ];
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::splat(-10.), Vec2::splat(10.)))
+        .with_duplicate_policy(DuplicatePolicy::Discard);
+    tree.construct_tree(&points);
+    assert_eq!(stored_items(&tree), vec![0, 4, 5]);
+
+    // insert compares with `==` and agrees
+    let mut points = points;
+    points.push(Vec2::new(-0., -0.));
+    tree.insert(points.len() - 1, &points).unwrap();
+    assert_eq!(stored_items(&tree), vec![0, 4, 5]);
+}
+
+#[test]
+fn morton_builds_the_same_tree() {
+    fastrand::seed(101);
+    // a dense cluster goes deeper than the morton codes do, so the regular
+    // partition has to finish it off
+    let mut points = random_points(4000, Vec2::splat(-20.), Vec2::splat(1020.));
+    points.extend(random_points(2000, Vec2::splat(400.), Vec2::splat(401.)));
+    points.extend(vec![Vec2::splat(700.); 30]);
+
+    let boundary = BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.));
+    let setups = [
+        QuadTree::build(4, boundary),
+        QuadTree::build(1, boundary).with_limits(6, 0.),
+        QuadTree::build(8, boundary).with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow),
+        QuadTree::build(4, boundary).with_duplicate_policy(DuplicatePolicy::Discard),
+    ];
+    setups.into_iter().for_each(|mut serial| {
+        let mut morton = QuadTree::build(serial.leaf_capacity, boundary)
+            .with_limits(serial.max_depth, serial.min_cell_size)
+            .with_out_of_bounds_policy(serial.out_of_bounds_policy)
+            .with_duplicate_policy(serial.duplicate_policy);
+        serial.construct_tree(&points);
+        morton.construct_tree_morton(&points);
+
+        assert!(morton.validate_stored(&points, &(0..points.len()).collect::<Vec<_>>()).is_ok());
+        assert_eq!(leaf_contents(&morton), leaf_contents(&serial));
+        assert_eq!(morton.overflow, serial.overflow);
+        assert_eq!(query_results(&morton, &points), query_results(&serial, &points));
+    });
+}
+
+#[test]
+fn morton_reorder_keeps_query_results() {
+    fastrand::seed(102);
+    let points = random_points(3000, Vec2::ZERO, Vec2::splat(1000.));
+    let mut serial = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    serial.construct_tree(&points);
+
+    let mut reordered = points.clone();
+    let mut morton = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    morton.construct_tree_morton(&points);
+    morton.reorder_items(&mut reordered);
+
+    // the indices change, the positions they point at don't
+    let to_positions = |results: Vec<Vec<usize>>, items: &[Vec2]| -> Vec<Vec<[u32; 2]>> {
+        results
+            .into_iter()
+            .map(|result| {
+                let mut positions: Vec<[u32; 2]> =
+                    result.into_iter().map(|index| items[index].to_array().map(f32::to_bits)).collect();
+                positions.sort_unstable();
+                positions
+            })
+            .collect()
+    };
+    assert_eq!(
+        to_positions(query_results(&morton, &reordered), &reordered),
+        to_positions(query_results(&serial, &points), &points)
+    );
+}
+
+/// a heavy clump in one corner plus a thin background, about as lopsided as
+/// the simulation gets
+fn clustered_particles() -> Vec<Particle> {
+    let mut positions = random_points(1500, Vec2::splat(100.), Vec2::splat(160.));
+    positions.extend(random_points(500, Vec2::ZERO, Vec2::splat(1000.)));
+    positions
+        .into_iter()
+        .map(|position| Particle::new(position, Vec2::ZERO, fastrand::f32() * 50. + 1.))
+        .collect()
+}
+
+#[test]
+fn split_policies_answer_queries_exactly() {
+    fastrand::seed(201);
+    let particles = clustered_particles();
+    let points: Vec<Vec2> = particles.iter().map(|particle| particle.position).collect();
+    let mut center = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    center.construct_tree(&points);
+
+    [SplitPolicy::Median, SplitPolicy::MassWeighted].into_iter().for_each(|split_policy| {
+        let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+            .with_split_policy(split_policy);
+        tree.construct_tree_weighted(&particles);
+        assert!(
+            tree.validate_stored(&points, &(0..points.len()).collect::<Vec<_>>()).is_ok(),
+            "{split_policy:?}"
+        );
+        assert_eq!(query_results(&tree, &points), query_results(&center, &points), "{split_policy:?}");
+    });
+}
+
+#[test]
+fn median_splits_evenly() {
+    fastrand::seed(202);
+    let points: Vec<Vec2> = clustered_particles().iter().map(|particle| particle.position).collect();
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+        .with_split_policy(SplitPolicy::Median);
+    tree.construct_tree(&points);
+
+    let mut xs: Vec<f32> = points.iter().map(|point| point.x).collect();
+    let mut ys: Vec<f32> = points.iter().map(|point| point.y).collect();
+    xs.sort_by(f32::total_cmp);
+    ys.sort_by(f32::total_cmp);
+    let middle = points.len() / 2;
+    assert_eq!(tree.split_point(QuadTree::ROOT_INDEX), Some(Vec2::new(xs[middle], ys[middle])));
+
+    // no quadrant ends up with more than half of its parent's items
+    let count_in = |boundary: &BoundingBox| points.iter().filter(|&&point| boundary.contains(point)).count();
+    tree.nodes.iter().filter_map(|node| node.leaves.map(|leaf_start| (node, leaf_start))).for_each(
+        |(stem, leaf_start)| {
+            let total = count_in(&stem.boundary);
+            (leaf_start..(leaf_start + QuadTree::STEM_LEAF_COUNT)).for_each(|leaf| {
+                assert!(count_in(&tree.nodes[leaf].boundary) <= total.div_ceil(2));
+            });
+        },
+    );
+
+    // which keeps the clump from dragging the tree down
+    let mut center = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    center.construct_tree(&points);
+    let deepest = |tree: &QuadTree| tree.nodes.iter().map(|node| node.depth).max().unwrap();
+    assert!(deepest(&tree) < deepest(&center));
+}
+
+#[test]
+fn mass_weighted_splits_at_the_center_of_mass() {
+    fastrand::seed(203);
+    let particles = clustered_particles();
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+        .with_split_policy(SplitPolicy::MassWeighted);
+
+    tree.construct_tree_weighted(&particles);
+    let total_mass: f32 = particles.iter().map(|particle| particle.mass).sum();
+    let center_of_mass =
+        particles.iter().map(|particle| particle.position * particle.mass).sum::<Vec2>() / total_mass;
+    let split = tree.split_point(QuadTree::ROOT_INDEX).unwrap();
+    assert!(split.distance(center_of_mass) < 1e-2, "{split} vs {center_of_mass}");
+
+    // unweighted every item counts as 1, so it is the plain centroid
+    tree.construct_tree(&particles);
+    let centroid = particles.iter().map(|particle| particle.position).sum::<Vec2>() / particles.len() as f32;
+    let split = tree.split_point(QuadTree::ROOT_INDEX).unwrap();
+    assert!(split.distance(centroid) < 1e-2, "{split} vs {centroid}");
+}
+
+#[test]
+fn reject_reports_exactly_the_offending_items() {
+    fastrand::seed(71);
+    let mut points = random_points(500, Vec2::ZERO, Vec2::splat(100.));
+    let outside = [
+        (3, Vec2::new(150., 50.)),
+        (40, Vec2::new(-0.5, 50.)),
+        // the max edge belongs to the next box over
+        (41, Vec2::new(50., 100.)),
+        (97, Vec2::new(f32::NAN, 20.)),
+        (98, Vec2::NAN),
+        (250, Vec2::new(f32::INFINITY, 0.)),
+        (251, Vec2::new(10., f32::NEG_INFINITY)),
+        (499, Vec2::new(1e30, -1e30)),
+    ];
+    outside.iter().for_each(|&(index, position)| points[index] = position);
+    let rejected: Vec<usize> = outside.iter().map(|&(index, _)| index).collect();
+    let accepted: Vec<usize> = (0..points.len()).filter(|index| !rejected.contains(index)).collect();
+
+    let boundary = BoundingBox::build(Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, boundary).with_out_of_bounds_policy(OutOfBoundsPolicy::Reject);
+    assert_eq!(tree.try_construct_tree(&points).unwrap_err().rejected, rejected);
+
+    // the rest is built exactly like it would be without the rejected items
+    let mut dropped = QuadTree::build(4, boundary);
+    dropped.construct_tree(&points);
+    assert_eq!(stored_items(&tree), accepted);
+    assert_eq!(leaf_contents(&tree), leaf_contents(&dropped));
+    assert_eq!(tree.validate_stored(&points, &accepted), Ok(()));
+    assert!(tree.overflow.is_empty());
+    assert_eq!(tree.nodes[QuadTree::ROOT_INDEX].boundary.min, Vec2::ZERO);
+    assert_eq!(tree.nodes[QuadTree::ROOT_INDEX].boundary.max, Vec2::splat(100.));
+
+    // a rejected insert doesn't touch the tree at all
+    let before = (leaf_contents(&tree), tree.item_indices.clone(), tree.nodes.len());
+    [Vec2::NAN, Vec2::new(f32::INFINITY, 5.), Vec2::new(5., 100.)].into_iter().for_each(|position| {
+        points.push(position);
+        let index = points.len() - 1;
+        assert_eq!(tree.insert(index, &points).unwrap_err().rejected, vec![index]);
+        assert_eq!((leaf_contents(&tree), tree.item_indices.clone(), tree.nodes.len()), before);
+    });
+
+    // nothing out of bounds is no error
+    let mut tree = QuadTree::build(4, boundary).with_out_of_bounds_policy(OutOfBoundsPolicy::Reject);
+    assert_eq!(tree.try_construct_tree(&random_points(100, Vec2::ZERO, Vec2::splat(100.))), Ok(()));
+}
+
+#[test]
+fn grow_on_construct_stores_every_item() {
+    fastrand::seed(72);
+    let mut points = random_points(1000, Vec2::ZERO, Vec2::splat(100.));
+    points.extend([Vec2::new(-500., 30.), Vec2::new(1e4, 2e3), Vec2::new(50., 100.), Vec2::splat(100.)]);
+    let everything: Vec<usize> = (0..points.len()).collect();
+
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Grow);
+    assert_eq!(tree.try_construct_tree(&points), Ok(()));
+    assert_eq!(stored_items(&tree), everything);
+    assert_eq!(tree.validate_stored(&points, &everything), Ok(()));
+    assert!(points.iter().all(|&point| tree.nodes[QuadTree::ROOT_INDEX].boundary.contains(point)));
+
+    // there is no boundary big enough for non-finite positions, they are the
+    // only ones left out
+    points[10] = Vec2::NAN;
+    points[20] = Vec2::new(f32::NEG_INFINITY, 0.);
+    let finite: Vec<usize> = everything.into_iter().filter(|&index| index != 10 && index != 20).collect();
+    tree.construct_tree(&points);
+    assert_eq!(stored_items(&tree), finite);
+    assert_eq!(tree.validate_stored(&points, &finite), Ok(()));
+}
@@ -0,0 +1,96 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::FmmSolver;
+use quadtree::ForceSolver;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+use quadtree::State;
+
+mod common;
+
+use common::direct_summation;
+use common::random_particles;
+use common::relative_errors;
+use common::PARAMETERS;
+
+#[test]
+fn fmm_matches_direct_summation() {
+    fastrand::seed(25);
+    let particles = random_particles(3000, Vec2::ZERO, Vec2::splat(1000.));
+    let mut tree = QuadTree::build(8, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    tree.construct_tree(&particles);
+
+    let exact = direct_summation(&particles, PARAMETERS);
+    let approximate = FmmSolver::build(6).with_opening(0.5).accelerations(&tree, &particles, PARAMETERS);
+    let (median, max) = relative_errors(&approximate, &exact);
+    assert!(median < 1e-4, "median error {median}");
+    assert!(max < 1e-2, "max error {max}");
+}
+
+#[test]
+fn error_falls_with_order() {
+    fastrand::seed(7);
+    let particles = random_particles(2000, Vec2::ZERO, Vec2::new(1920., 1080.));
+    let mut tree = QuadTree::build(3, BoundingBox::build(Vec2::ZERO, Vec2::new(1920., 1080.)));
+    tree.construct_tree(&particles);
+
+    let exact = direct_summation(&particles, PARAMETERS);
+    let medians: Vec<f32> = [1, 2, 4, 8]
+        .into_iter()
+        .map(|order| {
+            let approximate = FmmSolver::build(order).accelerations(&tree, &particles, PARAMETERS);
+            relative_errors(&approximate, &exact).0
+        })
+        .collect();
+    assert!(medians.windows(2).all(|pair| pair[1] < pair[0]), "median errors {medians:?}");
+    assert!(medians[3] < 1e-4, "median errors {medians:?}");
+}
+
+#[test]
+fn clustered_and_overflow_particles() {
+    fastrand::seed(3);
+    // a tight clump makes a deep, lopsided tree, the stragglers end up outside
+    // of the root
+    let mut particles = random_particles(1500, Vec2::splat(480.), Vec2::splat(520.));
+    particles.extend(random_particles(500, Vec2::ZERO, Vec2::splat(1000.)));
+    particles.extend(random_particles(20, Vec2::splat(1100.), Vec2::splat(1300.)));
+    [SplitPolicy::Center, SplitPolicy::Median].into_iter().for_each(|split_policy| {
+        let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+            .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow)
+            .with_split_policy(split_policy);
+        tree.construct_tree(&particles);
+        assert_eq!(tree.overflow.len(), 20);
+
+        let exact = direct_summation(&particles, PARAMETERS);
+        let approximate = FmmSolver::build(8).with_opening(0.5).accelerations(&tree, &particles, PARAMETERS);
+        let (median, max) = relative_errors(&approximate, &exact);
+        assert!(median < 1e-4, "{split_policy:?}: median error {median}");
+        assert!(max < 1e-2, "{split_policy:?}: max error {max}");
+
+        // the overflow is summed directly both ways, so it is as good as exact
+        let (_, overflow_max) = relative_errors(&approximate[2000..], &exact[2000..]);
+        assert!(overflow_max < 1e-4, "{split_policy:?}: overflow error {overflow_max}");
+    });
+}
+
+#[test]
+fn state_runs_fmm_when_selected() {
+    fastrand::seed(11);
+    let mut state = State::build(1000, 1000);
+    state.config.force_solver = ForceSolver::Fmm;
+    state.config.fmm_order = 8;
+    (0..1000).for_each(|_| {
+        state.spawn_particle(Vec2::new(fastrand::f32() * 1000., fastrand::f32() * 1000.));
+    });
+
+    let exact = direct_summation(&state.particles, state.config.force_parameters());
+    let dt = 1.;
+    state.update_barnes_hut(dt);
+
+    // particles spawn at rest, so the step's velocity is all acceleration
+    let step = dt * state.config.frame_time_dt_mod;
+    let approximate: Vec<Vec2> = state.particles.iter().map(|particle| particle.velocity / step).collect();
+    let (median, _) = relative_errors(&approximate, &exact);
+    assert!(median < 1e-3, "median error {median}");
+}
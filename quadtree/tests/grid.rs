@@ -0,0 +1,117 @@
+use std::time::Duration;
+use std::time::Instant;
+
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::SpatialHash;
+use quadtree::UniformGrid;
+
+mod common;
+
+use common::brute_force_nearest;
+use common::brute_force_radius;
+use common::brute_force_range;
+use common::random_points;
+use common::sorted;
+
+/// random range, radius and nearest queries checked against brute force.
+/// `query_range`, `query_radius` and `nearest` are handed in so the grid and
+/// the hash can share it
+fn assert_matches_brute_force(
+    points: &[Vec2], query_min: Vec2, query_max: Vec2, query_range: impl Fn(&BoundingBox) -> Vec<usize>,
+    query_radius: impl Fn(Vec2, f32) -> Vec<usize>, nearest: impl Fn(Vec2, usize) -> Vec<(usize, f32)>,
+) {
+    (0..100).for_each(|_| {
+        let corners = random_points(2, query_min, query_max);
+        let boundary = BoundingBox::build(corners[0].min(corners[1]), corners[0].max(corners[1]));
+        assert_eq!(sorted(query_range(&boundary)), brute_force_range(points, &boundary));
+
+        let center = random_points(1, query_min, query_max)[0];
+        let radius = fastrand::f32() * 400.;
+        assert_eq!(sorted(query_radius(center, radius)), brute_force_radius(points, center, radius));
+
+        let count = [1, 5, 40][fastrand::usize(..3)];
+        assert_eq!(nearest(center, count), brute_force_nearest(points, center, count));
+    });
+}
+
+#[test]
+fn grid_matches_brute_force() {
+    fastrand::seed(161);
+    // some of the points are outside and get filed under the edge cells
+    let points = random_points(3000, Vec2::splat(-200.), Vec2::splat(1200.));
+    [7., 60., 500.].into_iter().for_each(|cell_size| {
+        let mut grid = UniformGrid::build(cell_size, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+        grid.construct_grid(&points);
+        assert_matches_brute_force(
+            &points,
+            Vec2::splat(-400.),
+            Vec2::splat(1400.),
+            |boundary| grid.query_range(boundary, &points),
+            |center, radius| grid.query_radius(center, radius, &points),
+            |point, count| grid.nearest(point, count, &points),
+        );
+    });
+}
+
+#[test]
+fn hash_matches_brute_force() {
+    fastrand::seed(162);
+    let points = random_points(3000, Vec2::splat(-600.), Vec2::splat(1600.));
+    [7., 60., 500.].into_iter().for_each(|cell_size| {
+        let mut hash = SpatialHash::build(cell_size, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+        hash.construct_hash(&points);
+        assert_matches_brute_force(
+            &points,
+            Vec2::splat(-800.),
+            Vec2::splat(1800.),
+            |boundary| hash.query_range(boundary, &points),
+            |center, radius| hash.query_radius(center, radius, &points),
+            |point, count| hash.nearest(point, count, &points),
+        );
+    });
+}
+
+#[test]
+fn hash_with_far_outliers() {
+    fastrand::seed(163);
+    [1e4, 1e6, 1e9].into_iter().for_each(|far| {
+        let mut points = random_points(2000, Vec2::ZERO, Vec2::splat(1000.));
+        points.push(Vec2::splat(far));
+        points.push(Vec2::new(-far, far / 2.));
+        let mut hash = SpatialHash::build(10., BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+        hash.construct_hash(&points);
+
+        // the occupied extent is huge but nearly all of it is empty, the
+        // queries shouldn't have to walk it
+        let start = Instant::now();
+        let everything = BoundingBox::build(Vec2::splat(-2. * far), Vec2::splat(2. * far));
+        assert_eq!(sorted(hash.query_range(&everything, &points)), (0..points.len()).collect::<Vec<_>>());
+        assert_eq!(
+            sorted(hash.query_radius(Vec2::ZERO, 4. * far, &points)),
+            (0..points.len()).collect::<Vec<_>>()
+        );
+        (0..50).for_each(|_| {
+            let point = random_points(1, Vec2::ZERO, Vec2::splat(1000.))[0];
+            assert_eq!(hash.nearest(point, 3, &points), brute_force_nearest(&points, point, 3));
+        });
+        let far_point = Vec2::splat(far * 0.9);
+        assert_eq!(hash.nearest(far_point, 2, &points), brute_force_nearest(&points, far_point, 2));
+        assert!(start.elapsed() < Duration::from_secs(2), "{far}: {:?}", start.elapsed());
+    });
+}
+
+#[test]
+fn empty_grid_and_hash() {
+    let points: Vec<Vec2> = Vec::new();
+    let boundary = BoundingBox::build(Vec2::ZERO, Vec2::splat(100.));
+    let mut grid = UniformGrid::build(10., boundary);
+    grid.construct_grid(&points);
+    let mut hash = SpatialHash::build(10., boundary);
+    hash.construct_hash(&points);
+
+    assert!(grid.query_range(&boundary, &points).is_empty());
+    assert!(grid.nearest(Vec2::splat(50.), 3, &points).is_empty());
+    assert!(hash.query_radius(Vec2::splat(50.), 80., &points).is_empty());
+    assert!(hash.nearest(Vec2::splat(50.), 3, &points).is_empty());
+}
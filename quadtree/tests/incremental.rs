@@ -0,0 +1,209 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::Particle;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+
+mod common;
+
+use common::random_points;
+
+fn random_box(min: f32, max: f32) -> BoundingBox {
+    let corners = random_points(2, Vec2::splat(min), Vec2::splat(max));
+    BoundingBox::build(corners[0].min(corners[1]), corners[0].max(corners[1]))
+}
+
+/// checks the tree against the items marked present, over the whole tree and
+/// a handful of random boxes
+fn assert_matches_brute_force(tree: &QuadTree, points: &[Vec2], present: &[bool]) {
+    let stored: Vec<usize> = (0..points.len()).filter(|&index| present[index]).collect();
+    assert_eq!(tree.validate_stored(points, &stored), Ok(()));
+
+    let everything = BoundingBox::build(Vec2::splat(f32::MIN), Vec2::splat(f32::MAX));
+    let boxes = std::iter::once(everything).chain((0..20).map(|_| random_box(-50., 150.)));
+    boxes.for_each(|boundary| {
+        let mut found = tree.query_range_exact(&boundary, points);
+        found.sort_unstable();
+        let expected: Vec<usize> =
+            (0..points.len()).filter(|&index| present[index] && boundary.contains(points[index])).collect();
+        assert_eq!(found, expected, "{boundary:?}");
+    });
+}
+
+#[test]
+fn insert_matches_brute_force() {
+    fastrand::seed(81);
+    let points = random_points(1500, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points[..500]);
+
+    let mut present: Vec<bool> = (0..points.len()).map(|index| index < 500).collect();
+    (500..points.len()).for_each(|index| {
+        tree.insert(index, &points).unwrap();
+        present[index] = true;
+    });
+    assert_matches_brute_force(&tree, &points, &present);
+}
+
+#[test]
+fn remove_matches_brute_force() {
+    fastrand::seed(82);
+    let points = random_points(1500, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    let mut present = vec![true; points.len()];
+    (0..700).for_each(|_| {
+        let index = fastrand::usize(..points.len());
+        assert_eq!(tree.remove(index, &points), present[index]);
+        present[index] = false;
+    });
+    assert_matches_brute_force(&tree, &points, &present);
+}
+
+#[test]
+fn relocate_matches_brute_force() {
+    fastrand::seed(83);
+    let mut points = random_points(1500, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    (0..3000).for_each(|step| {
+        let index = fastrand::usize(..points.len());
+        let old_position = points[index];
+        // mostly small nudges that stay in the leaf, sometimes a jump across
+        points[index] = if step % 4 == 0 {
+            random_points(1, Vec2::ZERO, Vec2::splat(100.))[0]
+        }
+        else {
+            (old_position + random_points(1, Vec2::splat(-1.), Vec2::splat(1.))[0])
+                .clamp(Vec2::ZERO, Vec2::splat(99.99))
+        };
+        tree.relocate(index, old_position, &points).unwrap();
+    });
+    assert_matches_brute_force(&tree, &points, &vec![true; points.len()]);
+}
+
+#[test]
+fn removing_collapses_stems() {
+    fastrand::seed(84);
+    let points = random_points(1000, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+    assert!(tree.nodes[QuadTree::ROOT_INDEX].leaves.is_some());
+
+    let mut present = vec![true; points.len()];
+    (4..points.len()).for_each(|index| {
+        assert!(tree.remove(index, &points));
+        present[index] = false;
+    });
+    assert_matches_brute_force(&tree, &points, &present);
+
+    // 4 items fit into one leaf, so everything collapsed back into the root
+    let root = tree.nodes[QuadTree::ROOT_INDEX];
+    assert!(root.leaves.is_none());
+    assert_eq!(root.data_len, 4);
+
+    // the detached nodes get reused when it splits again
+    let node_count = tree.nodes.len();
+    (4..200).for_each(|index| {
+        tree.insert(index, &points).unwrap();
+        present[index] = true;
+    });
+    assert_eq!(tree.nodes.len(), node_count);
+    assert_matches_brute_force(&tree, &points, &present);
+}
+
+#[test]
+fn grow_keeps_removed_items_out() {
+    let mut points: Vec<Vec2> = (0..19).map(|index| Vec2::new(index as f32 * 5., 50.)).collect();
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Grow);
+    tree.construct_tree(&points);
+    assert!(tree.remove(3, &points));
+
+    points.push(Vec2::new(250., -80.));
+    tree.insert(19, &points).unwrap();
+    assert!(tree.nodes[QuadTree::ROOT_INDEX].boundary.contains(points[19]));
+
+    let mut present = vec![true; points.len()];
+    present[3] = false;
+    assert_matches_brute_force(&tree, &points, &present);
+}
+
+#[test]
+fn grow_only_reinserts_what_the_tree_holds() {
+    fastrand::seed(85);
+    let mut points = random_points(1000, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Grow);
+    tree.construct_tree(&points[..600]);
+
+    let mut present: Vec<bool> = (0..points.len()).map(|index| index < 600).collect();
+    (0..200).for_each(|_| {
+        let index = fastrand::usize(..600);
+        tree.remove(index, &points);
+        present[index] = false;
+    });
+
+    // every few inserts lands outside and makes the root grow again
+    (600..points.len()).for_each(|index| {
+        if index % 50 == 0 {
+            points[index] = random_points(1, Vec2::splat(-400.), Vec2::splat(500.))[0];
+        }
+        tree.insert(index, &points).unwrap();
+        present[index] = true;
+    });
+    assert_matches_brute_force(&tree, &points, &present);
+}
+
+#[test]
+fn grow_keeps_the_weights() {
+    fastrand::seed(86);
+    // the left half is a lot heavier, so the center of mass is far from the
+    // plain centroid
+    let mut particles: Vec<Particle> = random_points(400, Vec2::ZERO, Vec2::splat(100.))
+        .into_iter()
+        .map(|position| Particle::new(position, Vec2::ZERO, if position.x < 50. { 100. } else { 1. }))
+        .collect();
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Grow)
+        .with_split_policy(SplitPolicy::MassWeighted);
+    tree.construct_tree_weighted(&particles[..399]);
+
+    particles[399] = Particle::new(Vec2::new(300., 250.), Vec2::ZERO, 5000.);
+    tree.insert_weighted(399, &particles).unwrap();
+    assert!(tree.nodes[QuadTree::ROOT_INDEX].boundary.contains(particles[399].position));
+
+    let total_mass: f32 = particles.iter().map(|particle| particle.mass).sum();
+    let center_of_mass =
+        particles.iter().map(|particle| particle.position * particle.mass).sum::<Vec2>() / total_mass;
+    let split = tree.split_point(QuadTree::ROOT_INDEX).unwrap();
+    assert!(split.distance(center_of_mass) < 1e-2, "{split} vs {center_of_mass}");
+    let everything: Vec<usize> = (0..particles.len()).collect();
+    assert_eq!(tree.validate_stored(&particles, &everything), Ok(()));
+}
+
+#[test]
+fn grow_drops_non_finite_items_without_rebuilding() {
+    fastrand::seed(87);
+    let mut points = random_points(300, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Grow);
+    tree.construct_tree(&points);
+    let boundary = tree.nodes[QuadTree::ROOT_INDEX].boundary;
+    let before = (tree.item_indices.clone(), tree.nodes.len());
+
+    [Vec2::NAN, Vec2::new(f32::INFINITY, 5.), Vec2::new(5., f32::NEG_INFINITY)].into_iter().for_each(
+        |position| {
+            points.push(position);
+            assert_eq!(tree.insert(points.len() - 1, &points), Ok(()));
+            assert_eq!((tree.item_indices.clone(), tree.nodes.len()), before);
+            assert_eq!(tree.nodes[QuadTree::ROOT_INDEX].boundary.min, boundary.min);
+            assert_eq!(tree.nodes[QuadTree::ROOT_INDEX].boundary.max, boundary.max);
+        },
+    );
+    let present: Vec<bool> = (0..points.len()).map(|index| index < 300).collect();
+    assert_matches_brute_force(&tree, &points, &present);
+}
@@ -0,0 +1,153 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::PositionPlanar;
+use quadtree::QuadTree;
+use quadtree::QuadTreeItems;
+use quadtree::QuadTreeOwner;
+
+mod common;
+
+use common::brute_force_nearest;
+use common::brute_force_radius;
+use common::brute_force_range;
+use common::random_points;
+use common::sorted;
+
+/// anything with a position works, the id makes it easy to tell which item
+/// came back
+#[derive(Debug, Clone, PartialEq)]
+struct Marker {
+    id: usize,
+    position: Vec2,
+}
+
+impl PositionPlanar for Marker {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
+}
+
+fn markers(points: &[Vec2]) -> Vec<Marker> {
+    points.iter().enumerate().map(|(id, &position)| Marker { id, position }).collect()
+}
+
+fn boundary() -> BoundingBox {
+    BoundingBox::build(Vec2::ZERO, Vec2::splat(100.))
+}
+
+fn ranges() -> Vec<BoundingBox> {
+    (0..20)
+        .map(|_| {
+            let min = Vec2::new(fastrand::f32(), fastrand::f32()) * 90.;
+            BoundingBox::build(min, min + Vec2::new(fastrand::f32(), fastrand::f32()) * 30.)
+        })
+        .collect()
+}
+
+fn ids<'a>(found: impl IntoIterator<Item = &'a Marker>) -> Vec<usize> {
+    sorted(found.into_iter().map(|marker| marker.id).collect())
+}
+
+/// the owned and borrowed wrappers, the owner and the bare tree all agree on
+/// every query, and with brute force
+fn assert_queries_agree(points: &[Vec2], items: &QuadTreeItems<Marker>, owner: &QuadTreeOwner<Marker>) {
+    let mut bare = QuadTree::build(4, boundary());
+    bare.construct_tree(points);
+    let borrowed = QuadTreeItems::build(4, boundary(), items.items());
+
+    ranges().iter().for_each(|range| {
+        let expected = brute_force_range(points, range);
+        assert_eq!(sorted(bare.query_range_exact(range, points)), expected);
+        assert_eq!(ids(items.query_range(range)), expected);
+        assert_eq!(ids(borrowed.query_range(range)), expected);
+        assert_eq!(ids(owner.query_range(range)), expected);
+    });
+
+    random_points(20, Vec2::ZERO, Vec2::splat(100.)).into_iter().for_each(|center| {
+        let expected = brute_force_radius(points, center, 12.);
+        assert_eq!(sorted(bare.query_radius(center, 12., points)), expected);
+        assert_eq!(ids(items.query_radius(center, 12.)), expected);
+        assert_eq!(ids(borrowed.query_radius(center, 12.)), expected);
+        assert_eq!(ids(owner.query_radius(center, 12.)), expected);
+
+        let expected: Vec<f32> =
+            brute_force_nearest(points, center, 7).into_iter().map(|(_, distance)| distance).collect();
+        let from_tree: Vec<f32> =
+            bare.nearest(center, 7, points).into_iter().map(|(_, distance)| distance).collect();
+        let wrapped: Vec<f32> =
+            items.nearest(center, 7).into_iter().map(|(_, _, distance)| distance).collect();
+        let owned: Vec<f32> = owner.nearest(center, 7).into_iter().map(|(_, distance)| distance).collect();
+        assert_eq!((from_tree, wrapped, owned), (expected.clone(), expected.clone(), expected));
+    });
+}
+
+#[test]
+fn wrappers_match_the_bare_tree() {
+    fastrand::seed(21);
+    let points = random_points(2000, Vec2::ZERO, Vec2::splat(100.));
+    let items = QuadTreeItems::build(4, boundary(), markers(&points));
+    let mut owner = QuadTreeOwner::build(4, boundary());
+    owner.init_tree(items.items());
+    assert_queries_agree(&points, &items, &owner);
+
+    // plain points go through the same paths
+    let plain = QuadTreeItems::build(4, boundary(), points.clone());
+    let range = BoundingBox::build(Vec2::splat(20.), Vec2::splat(45.));
+    let found: Vec<Vec2> = plain.query_range(&range).into_iter().copied().collect();
+    assert_eq!(found.len(), brute_force_range(&points, &range).len());
+    assert!(found.iter().all(|&point| range.contains(point)));
+}
+
+#[test]
+fn indexed_results_point_at_the_items() {
+    fastrand::seed(22);
+    let points = random_points(1000, Vec2::ZERO, Vec2::splat(100.));
+    let items = QuadTreeItems::build(4, boundary(), markers(&points));
+    let same_item = |(item, index): (&Marker, usize)| {
+        assert!(std::ptr::eq(item, items.get(index)));
+        assert_eq!(item.id, index);
+    };
+
+    let range = BoundingBox::build(Vec2::splat(10.), Vec2::splat(60.));
+    let found = items.query_range_indexed(&range);
+    assert_eq!(sorted(found.iter().map(|&(_, index)| index).collect()), brute_force_range(&points, &range));
+    found.into_iter().for_each(same_item);
+
+    let found = items.query_radius_indexed(Vec2::splat(50.), 20.);
+    assert_eq!(
+        sorted(found.iter().map(|&(_, index)| index).collect()),
+        brute_force_radius(&points, Vec2::splat(50.), 20.)
+    );
+    found.into_iter().for_each(same_item);
+
+    let found = items.nearest(Vec2::splat(33.), 5);
+    assert_eq!(found.len(), 5);
+    found.iter().for_each(|&(item, index, distance)| {
+        same_item((item, index));
+        assert_eq!(distance, item.position.distance(Vec2::splat(33.)));
+    });
+    let (item, index, _) = items.nearest_one(Vec2::splat(33.)).unwrap();
+    assert_eq!((item.id, index), (found[0].0.id, found[0].1));
+}
+
+#[test]
+fn rebuilding_after_mutating_stays_consistent() {
+    fastrand::seed(23);
+    let points = random_points(1500, Vec2::ZERO, Vec2::splat(100.));
+    let mut items = QuadTreeItems::build(4, boundary(), markers(&points));
+    let mut owner = QuadTreeOwner::build(4, boundary());
+
+    // mirror everything and squash it into one corner, so the old tree would
+    // give wrong answers everywhere
+    let moved: Vec<Vec2> = points.iter().map(|&point| (Vec2::splat(100.) - point) * 0.4).collect();
+    items.items.iter_mut().zip(&moved).for_each(|(marker, &position)| marker.position = position);
+    items.construct_tree();
+    owner.init_tree(items.items());
+
+    let ids: Vec<usize> = (0..points.len()).collect();
+    assert_eq!(items.tree.validate_stored(items.items(), &ids), Ok(()));
+    assert_queries_agree(&moved, &items, &owner);
+
+    let markers = items.into_items();
+    assert!(markers.iter().zip(&moved).all(|(marker, &position)| marker.position == position));
+}
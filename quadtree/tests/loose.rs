@@ -0,0 +1,113 @@
+use glam::Vec2;
+use quadtree::BoundedPlanar;
+use quadtree::BoundingBox;
+use quadtree::LooseQuadTree;
+use quadtree::Particle;
+
+mod common;
+
+use common::sorted;
+
+/// particles with a spread of radii, some of them huge and some centered
+/// outside of the root
+fn random_particles(count: usize) -> Vec<Particle> {
+    (0..count)
+        .map(|_| {
+            let position = Vec2::new(fastrand::f32(), fastrand::f32()) * 1200. - Vec2::splat(100.);
+            let mut particle = Particle::new(position, Vec2::ZERO, 1.);
+            particle.radius =
+                if fastrand::usize(..50) == 0 { fastrand::f32() * 200. } else { fastrand::f32() * 8. };
+            particle
+        })
+        .collect()
+}
+
+fn trees(particles: &[Particle]) -> Vec<LooseQuadTree> {
+    [1., LooseQuadTree::DEFAULT_LOOSENESS, 3.]
+        .into_iter()
+        .map(|looseness| {
+            let mut tree = LooseQuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+                .with_looseness(looseness);
+            tree.construct_tree(particles);
+            tree
+        })
+        .collect()
+}
+
+#[test]
+fn every_item_is_stored_once_inside_its_loose_node() {
+    fastrand::seed(121);
+    let particles = random_particles(3000);
+    trees(&particles).iter().for_each(|tree| {
+        let mut stored = tree.overflow.clone();
+        (0..tree.nodes.len()).for_each(|node_index| {
+            tree.node_items(node_index).iter().for_each(|&item_index| {
+                // the root takes anything centered in it, however big
+                if node_index != 0 {
+                    assert!(tree.loose_bounds[node_index].contains_box(&particles[item_index].bounds()));
+                }
+                stored.push(item_index);
+            });
+        });
+        assert_eq!(sorted(stored), (0..particles.len()).collect::<Vec<_>>());
+        tree.overflow.iter().for_each(|&item_index| {
+            assert!(!tree.nodes[0].boundary.contains(particles[item_index].position));
+        });
+    });
+}
+
+#[test]
+fn range_matches_brute_force() {
+    fastrand::seed(122);
+    let particles = random_particles(3000);
+    trees(&particles).iter().for_each(|tree| {
+        (0..200).for_each(|_| {
+            let corners =
+                [0, 1].map(|_| Vec2::new(fastrand::f32(), fastrand::f32()) * 1400. - Vec2::splat(200.));
+            let boundary = BoundingBox::build(corners[0].min(corners[1]), corners[0].max(corners[1]));
+            let expected: Vec<usize> =
+                (0..particles.len()).filter(|&index| particles[index].bounds().overlaps(&boundary)).collect();
+            assert_eq!(sorted(tree.query_range(&boundary, &particles)), expected);
+        });
+    });
+}
+
+#[test]
+fn circle_matches_brute_force() {
+    fastrand::seed(123);
+    let particles = random_particles(3000);
+    trees(&particles).iter().for_each(|tree| {
+        (0..200).for_each(|_| {
+            let center = Vec2::new(fastrand::f32(), fastrand::f32()) * 1400. - Vec2::splat(200.);
+            let radius = fastrand::f32() * 150.;
+
+            let expected: Vec<usize> = (0..particles.len())
+                .filter(|&index| particles[index].bounds().distance_squared(center) <= radius * radius)
+                .collect();
+            assert_eq!(sorted(tree.query_circle(center, radius, &particles)), expected);
+
+            let expected: Vec<usize> = (0..particles.len())
+                .filter(|&index| {
+                    let reach = radius + particles[index].radius;
+                    particles[index].position.distance_squared(center) <= reach * reach
+                })
+                .collect();
+            assert_eq!(sorted(tree.query_circle_round(center, radius, &particles)), expected);
+        });
+    });
+}
+
+#[test]
+fn round_items_near_the_box_corner() {
+    // the query circle reaches into the particle's bounding box corner but
+    // stays clear of the particle itself
+    let particles = vec![Particle::new(Vec2::splat(500.), Vec2::ZERO, 1000.)];
+    let radius = particles[0].radius;
+    let mut tree = LooseQuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    tree.construct_tree(&particles);
+
+    let center = Vec2::splat(500. + radius + 1.);
+    assert_eq!(tree.query_circle(center, 2., &particles), vec![0]);
+    assert!(tree.query_circle_round(center, 2., &particles).is_empty());
+    assert_eq!(tree.query_circle_round(center, (2_f32.sqrt() - 1.) * radius + 2., &particles), vec![0]);
+}
@@ -0,0 +1,131 @@
+use glam::Vec3;
+use quadtree::BoundingBox3;
+use quadtree::Octree;
+use quadtree::TreeHierarchy;
+
+mod common;
+
+use common::random_points_3d;
+use common::sorted;
+
+/// mostly inside the root, a dense clump and a few outside of it
+fn clustered_points() -> Vec<Vec3> {
+    let mut points = random_points_3d(4000, Vec3::ZERO, Vec3::splat(1000.));
+    points.extend(random_points_3d(1500, Vec3::new(600., 200., 300.), Vec3::new(640., 230., 310.)));
+    points.extend(random_points_3d(100, Vec3::splat(-400.), Vec3::splat(-1.)));
+
+    points
+}
+
+fn trees(points: &[Vec3]) -> Vec<Octree> {
+    [1, 4, 32]
+        .into_iter()
+        .map(|leaf_capacity| {
+            let mut tree = Octree::build(leaf_capacity, BoundingBox3::build(Vec3::ZERO, Vec3::splat(1000.)));
+            tree.construct_tree(points);
+            tree
+        })
+        .collect()
+}
+
+#[test]
+fn every_item_is_stored_once_inside_its_leaf() {
+    fastrand::seed(191);
+    let points = clustered_points();
+    trees(&points).iter().for_each(|tree| {
+        assert_eq!(tree.nodes.len() % Octree::STEM_LEAF_COUNT, 1);
+        let mut stored = tree.overflow.clone();
+        (0..tree.nodes.len()).for_each(|node_index| {
+            let node = &tree.nodes[node_index];
+            match node.leaves {
+                Some(leaf_start) => {
+                    assert!(tree.leaf_items(node_index).is_empty());
+                    (leaf_start..(leaf_start + Octree::STEM_LEAF_COUNT)).for_each(|leaf| {
+                        assert_eq!(tree.nodes[leaf].depth, node.depth + 1);
+                    });
+                }
+                None => {
+                    assert!(node.data_len <= tree.leaf_capacity);
+                    tree.leaf_items(node_index).iter().for_each(|&item_index| {
+                        assert!(node.boundary.contains(points[item_index]));
+                        stored.push(item_index);
+                    });
+                }
+            }
+        });
+        assert_eq!(sorted(stored), (0..points.len()).collect::<Vec<_>>());
+        tree.overflow.iter().for_each(|&item_index| {
+            assert!(!tree.nodes[Octree::ROOT_INDEX].boundary.contains(points[item_index]));
+        });
+    });
+}
+
+#[test]
+fn queries_match_brute_force() {
+    fastrand::seed(192);
+    let points = clustered_points();
+    trees(&points).iter().for_each(|tree| {
+        (0..200).for_each(|_| {
+            let corners = random_points_3d(2, Vec3::splat(-500.), Vec3::splat(1200.));
+            let boundary = BoundingBox3::build(corners[0].min(corners[1]), corners[0].max(corners[1]));
+            let expected: Vec<usize> =
+                (0..points.len()).filter(|&index| boundary.contains(points[index])).collect();
+            assert_eq!(sorted(tree.query_range(&boundary, &points)), expected);
+
+            let center = random_points_3d(1, Vec3::splat(-500.), Vec3::splat(1200.))[0];
+            let radius = fastrand::f32() * 300.;
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|&index| points[index].distance_squared(center) <= radius * radius)
+                .collect();
+            assert_eq!(sorted(tree.query_radius(center, radius, &points)), expected);
+
+            let count = [1, 6, 50][fastrand::usize(..3)];
+            let mut expected: Vec<(usize, f32)> =
+                (0..points.len()).map(|index| (index, points[index].distance_squared(center))).collect();
+            expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            expected.truncate(count);
+            let expected: Vec<(usize, f32)> = expected
+                .into_iter()
+                .map(|(index, distance_squared)| (index, distance_squared.sqrt()))
+                .collect();
+            assert_eq!(tree.nearest(center, count, &points), expected);
+        });
+    });
+}
+
+#[test]
+fn coincident_points_and_max_depth() {
+    // nothing can separate the coincident points, and the pair closer than
+    // max_depth allows stays in one leaf over capacity
+    let mut points = vec![Vec3::splat(300.); 20];
+    points.push(Vec3::splat(700.));
+    points.push(Vec3::new(100., 100., 100.));
+    points.push(Vec3::new(100.001, 100., 100.));
+    let mut tree = Octree::build(1, BoundingBox3::build(Vec3::ZERO, Vec3::splat(1000.))).with_max_depth(4);
+    tree.construct_tree(&points);
+
+    assert!(tree.nodes.iter().all(|node| node.depth <= 4));
+    let coincident = tree.nodes.iter().position(|node| node.data_len == 20).unwrap();
+    assert!(tree.nodes[coincident].leaves.is_none());
+    let close = (0..tree.nodes.len()).find(|&node_index| tree.leaf_items(node_index).contains(&21)).unwrap();
+    assert_eq!(sorted(tree.leaf_items(close).to_vec()), vec![21, 22]);
+    assert_eq!(tree.nodes[close].depth, 4);
+}
+
+#[test]
+fn empty_and_tiny_trees() {
+    let boundary = BoundingBox3::build(Vec3::ZERO, Vec3::splat(1000.));
+    let mut tree = Octree::build(4, boundary);
+    tree.construct_tree::<Vec3>(&[]);
+    assert_eq!(tree.node_count(), 1);
+    assert!(tree.nearest(Vec3::splat(5.), 3, &[] as &[Vec3]).is_empty());
+
+    let points = vec![Vec3::splat(10.), Vec3::splat(2000.)];
+    tree.construct_tree(&points);
+    assert_eq!(tree.overflow, vec![1]);
+    assert!(tree.nearest(Vec3::ZERO, 0, &points).is_empty());
+    assert_eq!(
+        tree.nearest(Vec3::ZERO, 5, &points).into_iter().map(|(index, _)| index).collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+}
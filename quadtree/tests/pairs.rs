@@ -0,0 +1,99 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::DuplicatePolicy;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::QuadTree;
+
+mod common;
+
+use common::random_points;
+
+fn brute_force_pairs(points: &[Vec2], distance: f32) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    (0..points.len()).for_each(|a| {
+        ((a + 1)..points.len()).for_each(|b| {
+            if points[a].distance_squared(points[b]) <= distance * distance {
+                pairs.push((a, b));
+            }
+        });
+    });
+
+    pairs
+}
+
+fn sorted_pairs(tree: &QuadTree, distance: f32, points: &[Vec2]) -> Vec<(usize, usize)> {
+    let mut pairs = tree.collision_pairs(distance, points);
+    pairs.sort_unstable();
+
+    pairs
+}
+
+#[test]
+fn pairs_match_brute_force() {
+    fastrand::seed(13);
+    let points = random_points(2000, Vec2::ZERO, Vec2::splat(1000.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    tree.construct_tree(&points);
+
+    [0., 1., 10., 35., 200.].into_iter().for_each(|distance| {
+        assert_eq!(sorted_pairs(&tree, distance, &points), brute_force_pairs(&points, distance));
+    });
+}
+
+#[test]
+fn pairs_are_reported_once_with_smaller_index_first() {
+    fastrand::seed(14);
+    let points = random_points(500, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(2, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    let mut pairs = Vec::new();
+    tree.for_each_pair_within(15., &points, |a, b, distance_squared| {
+        assert!(a < b);
+        assert_eq!(distance_squared, points[a].distance_squared(points[b]));
+        pairs.push((a, b));
+    });
+    let count = pairs.len();
+    pairs.sort_unstable();
+    pairs.dedup();
+    assert_eq!(pairs.len(), count);
+    assert_eq!(pairs, brute_force_pairs(&points, 15.));
+}
+
+#[test]
+fn pairs_include_coincident_points() {
+    let mut points = vec![Vec2::splat(10.); 50];
+    points.extend(random_points(50, Vec2::ZERO, Vec2::splat(100.)));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_duplicate_policy(DuplicatePolicy::Keep);
+    tree.construct_tree(&points);
+
+    assert_eq!(sorted_pairs(&tree, 5., &points), brute_force_pairs(&points, 5.));
+}
+
+#[test]
+fn pairs_include_overflow_items() {
+    fastrand::seed(15);
+    let points = random_points(1000, Vec2::splat(-50.), Vec2::splat(150.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow);
+    tree.construct_tree(&points);
+
+    assert_eq!(sorted_pairs(&tree, 12., &points), brute_force_pairs(&points, 12.));
+}
+
+#[test]
+fn pairs_after_incremental_updates() {
+    fastrand::seed(16);
+    let mut points = random_points(800, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(3, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    (0..200).for_each(|index| {
+        let old_position = points[index];
+        points[index] = random_points(1, Vec2::ZERO, Vec2::splat(100.))[0];
+        tree.relocate(index, old_position, &points).unwrap();
+    });
+
+    assert_eq!(sorted_pairs(&tree, 8., &points), brute_force_pairs(&points, 8.));
+}
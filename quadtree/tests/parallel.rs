@@ -0,0 +1,111 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::Particle;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+use quadtree::State;
+
+mod common;
+
+use common::random_particles;
+
+/// clumped so the quadrants are far from even, with some stragglers outside
+fn clustered_particles() -> Vec<Particle> {
+    let mut particles = random_particles(12000, Vec2::ZERO, Vec2::splat(1000.));
+    particles.extend(random_particles(7000, Vec2::new(600., 200.), Vec2::new(700., 260.)));
+    particles.extend(random_particles(900, Vec2::splat(100.), Vec2::splat(101.)));
+    particles.extend(random_particles(100, Vec2::splat(-300.), Vec2::splat(-1.)));
+
+    particles
+}
+
+/// boundary bits, leaves, data_head, data_len, depth
+type NodeFields = ([u32; 4], Option<usize>, Option<usize>, usize, usize);
+
+/// every node field, as bits so it can be compared exactly
+fn node_layout(tree: &QuadTree) -> Vec<NodeFields> {
+    tree.nodes
+        .iter()
+        .map(|node| {
+            let boundary = node.boundary;
+            let corners = [boundary.min.x, boundary.min.y, boundary.max.x, boundary.max.y].map(f32::to_bits);
+            (corners, node.leaves, node.data_head, node.data_len, node.depth)
+        })
+        .collect()
+}
+
+#[test]
+fn parallel_build_is_identical_to_serial() {
+    fastrand::seed(111);
+    let particles = clustered_particles();
+    [SplitPolicy::Center, SplitPolicy::Median, SplitPolicy::MassWeighted].into_iter().for_each(
+        |split_policy| {
+            let build = || {
+                QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+                    .with_split_policy(split_policy)
+                    .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow)
+            };
+            let mut serial = build();
+            serial.construct_tree_weighted(&particles);
+
+            [1, 2, 3, 4, 7, 16].into_iter().for_each(|threads| {
+                let mut parallel = build();
+                parallel.construct_tree_parallel_weighted(&particles, threads);
+                assert!(
+                    node_layout(&parallel) == node_layout(&serial),
+                    "{split_policy:?} on {threads} threads"
+                );
+                assert_eq!(
+                    parallel.item_indices, serial.item_indices,
+                    "{split_policy:?} on {threads} threads"
+                );
+                assert_eq!(parallel.overflow, serial.overflow, "{split_policy:?} on {threads} threads");
+            });
+        },
+    );
+}
+
+#[test]
+fn unweighted_parallel_build_is_identical_to_serial() {
+    fastrand::seed(112);
+    let particles = clustered_particles();
+    let mut serial = QuadTree::build(8, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    serial.construct_tree(&particles);
+
+    [2, 5, 64].into_iter().for_each(|threads| {
+        let mut parallel = QuadTree::build(8, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+        parallel.construct_tree_parallel(&particles, threads);
+        assert!(node_layout(&parallel) == node_layout(&serial), "{threads} threads");
+        assert_eq!(parallel.item_indices, serial.item_indices, "{threads} threads");
+    });
+
+    // a tree too small to split at all
+    let mut parallel = QuadTree::build(8, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    parallel.construct_tree_parallel(&particles[..5], 4);
+    assert_eq!(parallel.nodes.len(), 1);
+    assert_eq!(parallel.item_indices, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn threaded_state_matches_serial_accelerations() {
+    fastrand::seed(113);
+    let particles = clustered_particles();
+    [SplitPolicy::Center, SplitPolicy::MassWeighted].into_iter().for_each(|split_policy| {
+        let step = |threads: usize| {
+            let mut state = State::build(1000, 1000);
+            state.quadtree.split_policy = split_policy;
+            state.config.threads = threads;
+            state.particles = particles.clone();
+            state.update_barnes_hut(1.);
+            state.particles
+        };
+
+        // everything starts at rest, so the velocities are the accelerations
+        let serial = step(1);
+        assert!(serial.iter().any(|particle| particle.velocity != Vec2::ZERO));
+        [3, 8].into_iter().for_each(|threads| {
+            assert!(step(threads) == serial, "{split_policy:?} on {threads} threads");
+        });
+    });
+}
@@ -0,0 +1,198 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+
+mod common;
+
+use common::random_points;
+use common::sorted;
+
+/// how far off an edge a point has to be for the brute force and the tree to
+/// agree on which side of it it is, after both did their own rounding
+const EDGE: f32 = 0.01;
+
+/// deliberately not square and not at the origin
+fn domain() -> BoundingBox {
+    BoundingBox::build(Vec2::new(-200., 100.), Vec2::new(800., 600.))
+}
+
+/// squared distance to the closest copy of `point`, trying up to three
+/// periods either way on each axis. that's enough for anything the tests
+/// query around, and the axes can be done separately
+fn minimum_image_distance_squared(point: Vec2, center: Vec2) -> f32 {
+    let size = domain().max - domain().min;
+    let closest = |offset: f32, size: f32| {
+        (-3..=3).map(|shift| (offset + size * shift as f32).powi(2)).fold(f32::INFINITY, f32::min)
+    };
+    closest(point.x - center.x, size.x) + closest(point.y - center.y, size.y)
+}
+
+/// whether some copy of the point is inside, axis by axis: the first copy at
+/// or past the box' min has to come before its max
+fn minimum_image_range(points: &[Vec2], boundary: &BoundingBox) -> Vec<usize> {
+    let size = domain().max - domain().min;
+    let inside = |point: Vec2| {
+        let first = point + size * ((boundary.min - point) / size).ceil();
+        first.cmplt(boundary.max).all()
+    };
+    (0..points.len()).filter(|&index| inside(points[index])).collect()
+}
+
+fn minimum_image_radius(points: &[Vec2], center: Vec2, radius: f32) -> Vec<usize> {
+    (0..points.len())
+        .filter(|&index| minimum_image_distance_squared(points[index], center) <= radius * radius)
+        .collect()
+}
+
+/// a few periodic setups, the overflow one also gets items from outside of
+/// the domain which wrap around like everything else
+fn trees(points: &[Vec2]) -> Vec<QuadTree> {
+    let mut trees = vec![
+        QuadTree::build(1, domain()).with_periodic(true),
+        QuadTree::build(8, domain()).with_periodic(true).with_split_policy(SplitPolicy::Median),
+        QuadTree::build(4, domain())
+            .with_periodic(true)
+            .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow),
+    ];
+    trees.iter_mut().for_each(|tree| tree.construct_tree(points));
+
+    trees
+}
+
+fn points() -> Vec<Vec2> {
+    let mut points = random_points(2500, domain().min, domain().max);
+    // hugging the edges, where the wrapping matters most
+    points.extend(random_points(200, domain().min, Vec2::new(800., 110.)));
+    points.extend(random_points(200, Vec2::new(790., 100.), domain().max));
+    points.extend(random_points(50, Vec2::new(-700., -300.), Vec2::new(1300., 1000.)));
+
+    points
+}
+
+/// all of the items the tree could have stored
+fn stored(tree: &QuadTree, points: &[Vec2], expected: Vec<usize>) -> Vec<usize> {
+    if tree.out_of_bounds_policy == OutOfBoundsPolicy::Overflow {
+        return expected;
+    }
+    expected.into_iter().filter(|&index| domain().contains(points[index])).collect()
+}
+
+#[test]
+fn range_matches_minimum_image() {
+    fastrand::seed(231);
+    let points = points();
+    let size = domain().max - domain().min;
+    trees(&points).iter().for_each(|tree| {
+        (0..100).for_each(|_| {
+            let min = random_points(1, domain().min - size, domain().max + size)[0];
+            // from tiny up to wider than the whole domain
+            let extent = match fastrand::usize(..3) {
+                0 => random_points(1, Vec2::ZERO, size / 2.)[0],
+                1 => random_points(1, size / 2., size)[0],
+                _ => random_points(1, Vec2::ZERO, size * 1.5)[0],
+            };
+            let boundary = BoundingBox::build(min, min + extent);
+            // points this close to an edge can go either way, depending on
+            // how the wrapped copies of the box got rounded
+            let inset = |by: f32| BoundingBox::build(boundary.min + by, boundary.max - by);
+            let surely_inside = stored(tree, &points, minimum_image_range(&points, &inset(EDGE)));
+            let maybe_inside = stored(tree, &points, minimum_image_range(&points, &inset(-EDGE)));
+            let found = sorted(tree.query_range_exact(&boundary, &points));
+            assert!(surely_inside.iter().all(|index| found.binary_search(index).is_ok()), "{boundary:?}");
+            assert!(found.iter().all(|index| maybe_inside.binary_search(index).is_ok()), "{boundary:?}");
+
+            // the coarse query is always a superset
+            let coarse = sorted(tree.query_range(&boundary));
+            assert!(found.iter().all(|index| coarse.binary_search(index).is_ok()));
+        });
+    });
+}
+
+#[test]
+fn radius_matches_minimum_image() {
+    fastrand::seed(232);
+    let points = points();
+    let size = domain().max - domain().min;
+    trees(&points).iter().for_each(|tree| {
+        (0..100).for_each(|_| {
+            let center = random_points(1, domain().min - size / 2., domain().max + size / 2.)[0];
+            // up to past the far corner, which takes in everything
+            let radius = fastrand::f32() * size.length() * 0.6;
+            let surely_inside = stored(tree, &points, minimum_image_radius(&points, center, radius - EDGE));
+            let maybe_inside = stored(tree, &points, minimum_image_radius(&points, center, radius + EDGE));
+
+            let found = tree.query_radius_squared(center, radius, &points);
+            let indices = sorted(found.iter().map(|&(index, _)| index).collect());
+            assert!(
+                surely_inside.iter().all(|index| indices.binary_search(index).is_ok()),
+                "{center} {radius}"
+            );
+            assert!(
+                indices.iter().all(|index| maybe_inside.binary_search(index).is_ok()),
+                "{center} {radius}"
+            );
+            // found once, however many copies of it are in range
+            assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+            found.iter().for_each(|&(index, distance_squared)| {
+                let expected = minimum_image_distance_squared(points[index], center);
+                assert!((distance_squared - expected).abs() <= 1e-3 * expected.max(1.));
+            });
+        });
+    });
+
+    // wider than the domain on both axes, every stored item comes back once
+    let tree = &trees(&points)[2];
+    assert_eq!(
+        sorted(tree.query_radius(Vec2::ZERO, size.length(), &points)),
+        (0..points.len()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn nearest_matches_minimum_image() {
+    fastrand::seed(233);
+    let points = points();
+    let size = domain().max - domain().min;
+    trees(&points).iter().for_each(|tree| {
+        let candidates = stored(tree, &points, (0..points.len()).collect());
+        (0..100).for_each(|_| {
+            let center = random_points(1, domain().min - size / 2., domain().max + size / 2.)[0];
+            let count = [1, 7, 60, 5000][fastrand::usize(..4)];
+
+            let mut expected: Vec<f32> = candidates
+                .iter()
+                .map(|&index| minimum_image_distance_squared(points[index], center).sqrt())
+                .collect();
+            expected.sort_by(f32::total_cmp);
+            expected.truncate(count);
+
+            let found = tree.nearest(center, count, &points);
+            assert_eq!(found.len(), expected.len());
+            found.iter().zip(&expected).for_each(|(&(index, distance), &expected)| {
+                assert!((distance - expected).abs() <= 1e-3 * expected.max(1.), "{center} {count}");
+                let own = minimum_image_distance_squared(points[index], center).sqrt();
+                assert!((distance - own).abs() <= 1e-3 * own.max(1.));
+            });
+        });
+    });
+}
+
+#[test]
+fn wrap_helpers() {
+    let domain = domain();
+    assert_eq!(domain.wrap_offset(Vec2::new(900., -400.)), Vec2::new(-100., 100.));
+    assert_eq!(domain.wrap_offset(Vec2::new(400., 200.)), Vec2::new(400., 200.));
+    assert_eq!(domain.wrap_point(Vec2::new(850., 50.)), Vec2::new(-150., 550.));
+    assert_eq!(domain.wrap_point(Vec2::new(-200., 100.)), Vec2::new(-200., 100.));
+
+    // a box on the left edge is close to points just inside the right one
+    let edge = BoundingBox::build(Vec2::new(-200., 300.), Vec2::new(-190., 310.));
+    assert_eq!(edge.wrapped_distance_squared(Vec2::new(795., 305.), &domain), 25.);
+    assert_eq!(edge.wrapped_distance_squared(Vec2::new(0., 305.), &domain), 190. * 190.);
+
+    let tree = QuadTree::build(4, domain).with_periodic(true);
+    assert_eq!(tree.periodic_domain().map(|domain| (domain.min, domain.max)), Some((domain.min, domain.max)));
+    assert!(QuadTree::build(4, domain).periodic_domain().is_none());
+}
@@ -0,0 +1,188 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::QuadTree;
+use quadtree::SplitPolicy;
+
+mod common;
+
+use common::brute_force_nearest;
+use common::brute_force_radius;
+use common::brute_force_range;
+use common::random_points;
+
+/// the same points under a few different tree setups, some of them hanging
+/// over the root so the overflow gets exercised too
+fn trees(points: &[Vec2]) -> Vec<QuadTree> {
+    let boundary = BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.));
+    let mut trees = vec![
+        QuadTree::build(1, boundary),
+        QuadTree::build(8, boundary).with_split_policy(SplitPolicy::Median),
+        QuadTree::build(4, boundary).with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow),
+    ];
+    trees.iter_mut().for_each(|tree| tree.construct_tree(points));
+
+    trees
+}
+
+#[test]
+fn exact_range_matches_brute_force() {
+    fastrand::seed(31);
+    let points = random_points(3000, Vec2::splat(-100.), Vec2::splat(1100.));
+    trees(&points).iter().for_each(|tree| {
+        (0..200).for_each(|_| {
+            let corners = random_points(2, Vec2::splat(-200.), Vec2::splat(1200.));
+            let boundary = BoundingBox::build(corners[0].min(corners[1]), corners[0].max(corners[1]));
+
+            let expected = brute_force_range(&points, &boundary);
+            let mut found = tree.query_range_exact(&boundary, &points);
+            found.sort_unstable();
+            if tree.out_of_bounds_policy == OutOfBoundsPolicy::Overflow {
+                assert_eq!(found, expected);
+            }
+            else {
+                // dropped items are never found, everything else is
+                let stored: Vec<usize> = expected
+                    .into_iter()
+                    .filter(|&index| tree.nodes[QuadTree::ROOT_INDEX].boundary.contains(points[index]))
+                    .collect();
+                assert_eq!(found, stored);
+            }
+
+            // the coarse query is always a superset
+            let coarse = tree.query_range(&boundary);
+            assert!(found.iter().all(|index| coarse.contains(index)));
+        });
+    });
+}
+
+#[test]
+fn exact_range_edges() {
+    // the boundary includes its min edge and excludes its max edge
+    let points = vec![Vec2::new(10., 10.), Vec2::new(20., 20.), Vec2::new(10., 20.), Vec2::new(15., 15.)];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    let boundary = BoundingBox::build(Vec2::splat(10.), Vec2::splat(20.));
+    let mut found = tree.query_range_exact(&boundary, &points);
+    found.sort_unstable();
+    assert_eq!(found, brute_force_range(&points, &boundary));
+    assert_eq!(found, vec![0, 3]);
+
+    // nothing is found by a box that misses the root completely
+    let outside = BoundingBox::build(Vec2::splat(200.), Vec2::splat(300.));
+    assert!(tree.query_range_exact(&outside, &points).is_empty());
+}
+
+#[test]
+fn radius_matches_brute_force() {
+    fastrand::seed(41);
+    let points = random_points(3000, Vec2::splat(-100.), Vec2::splat(1100.));
+    trees(&points).iter().for_each(|tree| {
+        (0..200).for_each(|_| {
+            let center = random_points(1, Vec2::splat(-200.), Vec2::splat(1200.))[0];
+            let radius = fastrand::f32() * 300.;
+
+            let mut expected = brute_force_radius(&points, center, radius);
+            if tree.out_of_bounds_policy != OutOfBoundsPolicy::Overflow {
+                expected.retain(|&index| tree.nodes[QuadTree::ROOT_INDEX].boundary.contains(points[index]));
+            }
+            let mut found = tree.query_radius(center, radius, &points);
+            found.sort_unstable();
+            assert_eq!(found, expected);
+        });
+    });
+}
+
+#[test]
+fn radius_squared_hands_back_distances() {
+    fastrand::seed(42);
+    let points = random_points(2000, Vec2::ZERO, Vec2::splat(1000.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)));
+    tree.construct_tree(&points);
+
+    (0..100).for_each(|_| {
+        let center = random_points(1, Vec2::ZERO, Vec2::splat(1000.))[0];
+        let found = tree.query_radius_squared(center, 75., &points);
+        found.iter().for_each(|&(index, distance_squared)| {
+            assert_eq!(distance_squared, points[index].distance_squared(center));
+        });
+
+        let mut found: Vec<usize> = found.into_iter().map(|(index, _)| index).collect();
+        found.sort_unstable();
+        assert_eq!(found, brute_force_radius(&points, center, 75.));
+    });
+}
+
+#[test]
+fn radius_edges() {
+    // the circle includes its edge, and a zero radius only finds exact hits
+    let points = vec![Vec2::new(50., 50.), Vec2::new(60., 50.), Vec2::new(50., 60.001)];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    let mut found = tree.query_radius(Vec2::new(50., 50.), 10., &points);
+    found.sort_unstable();
+    assert_eq!(found, vec![0, 1]);
+    assert_eq!(tree.query_radius(Vec2::new(50., 50.), 0., &points), vec![0]);
+    assert!(tree.query_radius(Vec2::new(-50., -50.), 10., &points).is_empty());
+}
+
+#[test]
+fn nearest_matches_brute_force() {
+    fastrand::seed(51);
+    let points = random_points(3000, Vec2::splat(-100.), Vec2::splat(1100.));
+    trees(&points).iter().for_each(|tree| {
+        let stored: Vec<Vec2> = if tree.out_of_bounds_policy == OutOfBoundsPolicy::Overflow {
+            points.clone()
+        }
+        else {
+            // dropped points get a far away stand in so indices still line up
+            points
+                .iter()
+                .map(|&point| {
+                    if tree.nodes[QuadTree::ROOT_INDEX].boundary.contains(point) {
+                        point
+                    }
+                    else {
+                        Vec2::splat(f32::MAX)
+                    }
+                })
+                .collect()
+        };
+
+        (0..200).for_each(|_| {
+            let point = random_points(1, Vec2::splat(-300.), Vec2::splat(1300.))[0];
+            let count = [1, 2, 7, 32, 100][fastrand::usize(..5)];
+
+            let expected = brute_force_nearest(&stored, point, count);
+            let found = tree.nearest(point, count, &points);
+            assert_eq!(found.len(), count);
+            found.iter().zip(&expected).for_each(|(found, expected)| {
+                assert_eq!(found.1, expected.1);
+                assert_eq!(found.1, points[found.0].distance(point));
+            });
+        });
+    });
+}
+
+#[test]
+fn nearest_counts() {
+    fastrand::seed(52);
+    let points = random_points(50, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    assert!(tree.nearest(Vec2::splat(50.), 0, &points).is_empty());
+
+    // asking for more than there is hands back everything, closest first
+    let everything = tree.nearest(Vec2::splat(50.), 80, &points);
+    assert_eq!(everything, brute_force_nearest(&points, Vec2::splat(50.), 80));
+    assert_eq!(everything.len(), 50);
+
+    let (index, distance) = tree.nearest_one(Vec2::new(-20., 130.), &points).unwrap();
+    assert_eq!((index, distance), brute_force_nearest(&points, Vec2::new(-20., 130.), 1)[0]);
+
+    let empty = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    assert_eq!(empty.nearest_one(Vec2::splat(50.), &points), None);
+}
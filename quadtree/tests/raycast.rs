@@ -0,0 +1,157 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::Particle;
+use quadtree::QuadTree;
+use quadtree::RayCaster;
+use quadtree::RayHit;
+
+/// circles of mixed sizes, a few of them big enough to reach well outside of
+/// the leaf they are stored in and a few centered outside of the root
+fn random_particles(count: usize) -> Vec<Particle> {
+    (0..count)
+        .map(|_| {
+            let position = Vec2::new(fastrand::f32(), fastrand::f32()) * 1100. - Vec2::splat(50.);
+            let mut particle = Particle::new(position, Vec2::ZERO, 1.);
+            particle.radius = fastrand::f32() * 6.;
+            if fastrand::usize(..100) == 0 {
+                particle.radius = 40. + fastrand::f32() * 120.;
+            }
+            particle
+        })
+        .collect()
+}
+
+/// where a normalized ray enters the circle, 0 if it starts inside. solved
+/// straight from |origin + t * direction - center| = radius
+fn brute_force_entry(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    if offset.length_squared() <= radius * radius {
+        return Some(0.);
+    }
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0. {
+        return None;
+    }
+
+    let t = -b - discriminant.sqrt();
+    (t >= 0.).then_some(t)
+}
+
+fn brute_force_hits(particles: &[Particle], origin: Vec2, direction: Vec2, max_t: f32) -> Vec<RayHit> {
+    let direction = direction.normalize();
+    let mut hits: Vec<RayHit> = (0..particles.len())
+        .filter_map(|index| {
+            let t = brute_force_entry(origin, direction, particles[index].position, particles[index].radius)?;
+            (t <= max_t).then_some(RayHit { index, t })
+        })
+        .collect();
+    hits.sort_by(|a, b| a.t.total_cmp(&b.t).then(a.index.cmp(&b.index)));
+
+    hits
+}
+
+fn random_ray() -> (Vec2, Vec2, f32) {
+    let origin = Vec2::new(fastrand::f32(), fastrand::f32()) * 1300. - Vec2::splat(150.);
+    let angle = fastrand::f32() * std::f32::consts::TAU;
+    (origin, Vec2::new(angle.cos(), angle.sin()) * (fastrand::f32() * 3. + 0.1), fastrand::f32() * 1500.)
+}
+
+fn tree_and_caster(particles: &[Particle], leaf_capacity: usize) -> (QuadTree, RayCaster) {
+    let mut tree = QuadTree::build(leaf_capacity, BoundingBox::build(Vec2::ZERO, Vec2::splat(1000.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow);
+    tree.construct_tree(particles);
+    let mut caster = RayCaster::new();
+    caster.build_hierarchy(&tree, particles);
+
+    (tree, caster)
+}
+
+#[test]
+fn raycast_all_matches_brute_force() {
+    fastrand::seed(141);
+    let particles = random_particles(3000);
+    [1, 4, 16].into_iter().for_each(|leaf_capacity| {
+        let (tree, caster) = tree_and_caster(&particles, leaf_capacity);
+        (0..300).for_each(|_| {
+            let (origin, direction, max_t) = random_ray();
+            let found = caster.raycast_all(&tree, origin, direction, max_t, &particles);
+            let expected = brute_force_hits(&particles, origin, direction, max_t);
+            assert_eq!(
+                found.iter().map(|hit| hit.index).collect::<Vec<_>>(),
+                expected.iter().map(|hit| hit.index).collect::<Vec<_>>()
+            );
+            found.iter().zip(&expected).for_each(|(found, expected)| {
+                assert!(
+                    (found.t - expected.t).abs() <= 1e-3 * expected.t.max(1.),
+                    "{found:?} vs {expected:?}"
+                );
+            });
+        });
+    });
+}
+
+#[test]
+fn raycast_finds_the_first_hit() {
+    fastrand::seed(142);
+    let particles = random_particles(3000);
+    let (tree, caster) = tree_and_caster(&particles, 4);
+    (0..500).for_each(|_| {
+        let (origin, direction, max_t) = random_ray();
+        let found = caster.raycast(&tree, origin, direction, max_t, &particles);
+        let expected = brute_force_hits(&particles, origin, direction, max_t).first().copied();
+        assert_eq!(found.map(|hit| hit.index), expected.map(|hit| hit.index));
+
+        // a segment is a ray that stops at its end
+        let end = origin + direction.normalize() * max_t;
+        let segment = caster.segment_cast(&tree, origin, end, &particles);
+        assert_eq!(segment.map(|hit| hit.index), expected.map(|hit| hit.index));
+    });
+}
+
+#[test]
+fn raycast_edges() {
+    let particles = vec![
+        Particle::new(Vec2::new(100., 100.), Vec2::ZERO, 1.),
+        Particle::new(Vec2::new(300., 100.), Vec2::ZERO, 1.),
+    ];
+    let (tree, caster) = tree_and_caster(&particles, 1);
+    let radius = particles[0].radius;
+
+    // starting inside a circle hits it at 0
+    let hit = caster.raycast(&tree, Vec2::new(100., 100.), Vec2::X, 1000., &particles).unwrap();
+    assert_eq!(hit, RayHit { index: 0, t: 0. });
+
+    let hit = caster.raycast(&tree, Vec2::new(0., 100.), Vec2::X * 5., 1000., &particles).unwrap();
+    assert_eq!(hit.index, 0);
+    assert!((hit.t - (100. - radius)).abs() < 1e-4);
+
+    // stopping short of the circle, pointing away from it, or no direction
+    assert!(caster.raycast(&tree, Vec2::new(0., 100.), Vec2::X, 50., &particles).is_none());
+    assert!(caster.raycast(&tree, Vec2::new(0., 100.), -Vec2::X, 1000., &particles).is_none());
+    assert!(caster.raycast_all(&tree, Vec2::new(0., 100.), Vec2::ZERO, 1000., &particles).is_empty());
+
+    // grazing the top of both circles
+    let hits = caster.raycast_all(&tree, Vec2::new(0., 100. + radius * 0.99), Vec2::X, 1000., &particles);
+    assert_eq!(hits.iter().map(|hit| hit.index).collect::<Vec<_>>(), vec![0, 1]);
+}
+
+#[test]
+fn tree_raycast_matches_the_caster() {
+    fastrand::seed(143);
+    let particles = random_particles(2000);
+    let (tree, caster) = tree_and_caster(&particles, 4);
+    (0..100).for_each(|_| {
+        let (origin, direction, max_t) = random_ray();
+        assert_eq!(
+            tree.raycast(origin, direction, max_t, &particles),
+            caster.raycast(&tree, origin, direction, max_t, &particles)
+        );
+        assert_eq!(
+            tree.raycast_all(origin, direction, max_t, &particles),
+            caster.raycast_all(&tree, origin, direction, max_t, &particles)
+        );
+    });
+}
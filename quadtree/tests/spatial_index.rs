@@ -0,0 +1,119 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::BruteForce;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::Particle;
+use quadtree::QuadTree;
+use quadtree::SpatialHash;
+use quadtree::SpatialIndex;
+use quadtree::State;
+use quadtree::UniformGrid;
+
+mod common;
+
+use common::random_points;
+use common::sorted;
+
+/// moves the index over to `bounds`, rebuilds it and checks it against brute
+/// force
+fn assert_resized_index_matches<I>(index: &mut I, bounds: BoundingBox, points: &[Vec2])
+where
+    I: SpatialIndex<Vec2>,
+{
+    index.set_bounds(bounds);
+    index.build(points);
+    let mut brute_force = BruteForce::default();
+    SpatialIndex::<Vec2>::build(&mut brute_force, points);
+
+    (0..50).for_each(|_| {
+        let corners = random_points(2, bounds.min, bounds.max);
+        let boundary = BoundingBox::build(corners[0].min(corners[1]), corners[0].max(corners[1]));
+        assert_eq!(
+            sorted(index.query_box(&boundary, points)),
+            sorted(brute_force.query_box(&boundary, points))
+        );
+
+        let center = random_points(1, bounds.min, bounds.max)[0];
+        assert_eq!(
+            sorted(index.query_radius(center, 250., points)),
+            sorted(brute_force.query_radius(center, 250., points))
+        );
+        let distances =
+            |found: Vec<(usize, f32)>| found.into_iter().map(|(_, distance)| distance).collect::<Vec<_>>();
+        assert_eq!(
+            distances(index.nearest(center, 8, points)),
+            distances(brute_force.nearest(center, 8, points))
+        );
+    });
+}
+
+#[test]
+fn backends_follow_new_bounds() {
+    fastrand::seed(151);
+    let small = BoundingBox::build(Vec2::ZERO, Vec2::splat(100.));
+    let large = BoundingBox::build(Vec2::new(-500., -200.), Vec2::new(3000., 2000.));
+    let points = random_points(2000, large.min, large.max);
+
+    assert_resized_index_matches(&mut QuadTree::build(4, small), large, &points);
+    assert_resized_index_matches(&mut UniformGrid::build(50., small), large, &points);
+    assert_resized_index_matches(&mut SpatialHash::build(50., small), large, &points);
+    assert_resized_index_matches(&mut BruteForce::default(), large, &points);
+}
+
+#[test]
+fn resized_grid_covers_the_new_bounds() {
+    let mut grid = UniformGrid::build(50., BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    grid.set_bounds(BoundingBox::build(Vec2::ZERO, Vec2::new(1000., 520.)));
+    assert_eq!((grid.columns, grid.rows), (20, 11));
+    assert_eq!(grid.cell_size, 50.);
+}
+
+#[test]
+fn update_dimensions_resizes_every_index() {
+    fastrand::seed(152);
+    let mut state = State::build(100, 100);
+    state.update_dimensions(4000., 3000.);
+    (0..1000).for_each(|_| {
+        state.spawn_particle(random_points(1, Vec2::ZERO, Vec2::new(4000., 3000.))[0]);
+    });
+
+    // nothing lands in the neighbor index' overflow, which every query would
+    // have to check
+    assert_eq!(state.neighbor_index.out_of_bounds_policy, OutOfBoundsPolicy::Overflow);
+    SpatialIndex::<Particle>::build(&mut state.neighbor_index, &state.particles);
+    assert!(state.neighbor_index.overflow.is_empty());
+    assert_eq!(state.neighbor_index.nodes[QuadTree::ROOT_INDEX].boundary.max, Vec2::new(4000., 3000.));
+
+    state.update_barnes_hut(1.);
+    assert!(state.quadtree.overflow.is_empty());
+
+    // any other neighbor index is resized the same way
+    let hash = SpatialHash::build(300., BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    let mut state = State::build_with_index(100, 100, hash);
+    state.update_dimensions(4000., 3000.);
+    assert_eq!(state.neighbor_index.bounds.max, Vec2::new(4000., 3000.));
+    assert_eq!(state.quadtree.nodes[QuadTree::ROOT_INDEX].boundary.max, Vec2::new(4000., 3000.));
+}
+
+#[test]
+fn state_runs_on_a_spatial_hash() {
+    fastrand::seed(153);
+    let bounds = BoundingBox::build(Vec2::ZERO, Vec2::new(1000., 800.));
+    let mut hashed = State::build_with_index(1000, 800, SpatialHash::build(300., bounds));
+    let mut tree = State::build(1000, 800);
+    random_points(500, Vec2::ZERO, bounds.max).into_iter().for_each(|position| {
+        hashed.spawn_particle(position);
+        tree.spawn_particle(position);
+    });
+
+    // picking and the neighbor forces come out the same as with the default
+    // quadtree
+    let target = hashed.particles[123].position;
+    assert_eq!(hashed.pick_particle(target), Some(123));
+    assert_eq!(hashed.pick_particle(target), tree.pick_particle(target));
+    hashed.update(1.);
+    tree.update(1.);
+    hashed.particles.iter().zip(&tree.particles).for_each(|(hashed, tree)| {
+        assert!(hashed.position.distance(tree.position) < 1e-3, "{hashed:?} vs {tree:?}");
+    });
+}
@@ -0,0 +1,82 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::QuadTree;
+use quadtree::QuadTreeNode;
+use quadtree::TreeStats;
+
+/// the min corner quadrant (node 2) is split again with one item in each of
+/// its leaves, nodes 1 and 3 are empty and node 4 holds two items on the same
+/// spot, which can't be split apart. the last point is outside of the root
+fn two_levels() -> (QuadTree, Vec<Vec2>) {
+    let points = vec![
+        Vec2::new(10., 10.),
+        Vec2::new(40., 10.),
+        Vec2::new(10., 40.),
+        Vec2::new(40., 40.),
+        Vec2::new(75., 75.),
+        Vec2::new(75., 75.),
+        Vec2::new(150., 50.),
+    ];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow);
+    tree.construct_tree(&points);
+    assert_eq!(tree.nodes.len(), 9);
+    assert_eq!(tree.nodes[2].leaves, Some(5));
+    assert_eq!(tree.leaf_items(4), &[4, 5]);
+
+    (tree, points)
+}
+
+#[test]
+fn stats_describe_the_tree() {
+    let (tree, _) = two_levels();
+    let stats = tree.stats();
+    assert_eq!(
+        TreeStats { heap_bytes: 0, ..stats.clone() },
+        TreeStats {
+            depth: 2,
+            node_count: 9,
+            stem_count: 2,
+            leaf_count: 7,
+            empty_leaf_count: 2,
+            item_count: 6,
+            overflow_count: 1,
+            occupancy_histogram: vec![2, 4, 1],
+            leaves_per_depth: vec![0, 3, 4],
+            mean_items_per_leaf: 1.2,
+            max_items_per_leaf: 2,
+            heap_bytes: 0,
+        }
+    );
+    assert!(stats.heap_bytes >= 9 * size_of::<QuadTreeNode>() + 6 * size_of::<usize>());
+
+    let line = stats.to_string();
+    assert!(line.starts_with("depth 2 nodes 9 (stems 2 leaves 7 empty 2) items 6 overflow 1"), "{line}");
+    assert!(line.ends_with("occupancy [2, 4, 1]"), "{line}");
+}
+
+#[test]
+fn stats_skip_detached_nodes() {
+    let (mut tree, points) = two_levels();
+    (0..3).for_each(|index| assert!(tree.remove(index, &points)));
+    assert_eq!(tree.nodes.len(), 9);
+
+    // node 2 collapsed back into a leaf, its old children are no longer counted
+    let stats = tree.stats();
+    assert_eq!((stats.node_count, stats.stem_count, stats.leaf_count), (5, 1, 4));
+    assert_eq!((stats.depth, stats.empty_leaf_count, stats.item_count), (1, 2, 3));
+    assert_eq!(stats.occupancy_histogram, vec![2, 1, 1]);
+    assert_eq!(stats.leaves_per_depth, vec![0, 4]);
+    assert_eq!(stats.mean_items_per_leaf, 1.5);
+}
+
+#[test]
+fn empty_tree_stats() {
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree::<Vec2>(&[]);
+    let stats = tree.stats();
+    assert_eq!((stats.node_count, stats.leaf_count, stats.empty_leaf_count, stats.depth), (1, 1, 1, 0));
+    assert_eq!(stats.occupancy_histogram, vec![1]);
+    assert_eq!(stats.mean_items_per_leaf, 0.);
+}
@@ -0,0 +1,156 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::NodeRef;
+use quadtree::QuadTree;
+use quadtree::TreeTraversal;
+use quadtree::Visit;
+use quadtree::Visitor;
+
+mod common;
+
+use common::random_points;
+use common::sorted;
+
+/// the min corner quadrant (node 2) is split again into nodes 5..9, nodes 1
+/// and 3 are empty leaves and node 4 holds the last point
+fn two_levels() -> QuadTree {
+    let points = vec![
+        Vec2::new(10., 10.),
+        Vec2::new(40., 10.),
+        Vec2::new(10., 40.),
+        Vec2::new(40., 40.),
+        Vec2::new(75., 75.),
+    ];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+    assert_eq!(tree.nodes.len(), 9);
+    assert_eq!(tree.nodes[2].leaves, Some(5));
+
+    tree
+}
+
+/// visitor that remembers the leaves it passes
+struct LeafCollector(Vec<usize>);
+
+impl Visitor<BoundingBox> for LeafCollector {
+    fn visit(&mut self, node: &NodeRef<'_, BoundingBox>) -> Visit {
+        if node.is_leaf() {
+            self.0.push(node.index);
+        }
+
+        Visit::Descend
+    }
+}
+
+fn walked(tree: &QuadTree, start: usize, visit: impl Fn(usize) -> Visit) -> Vec<usize> {
+    let mut seen = Vec::new();
+    tree.walk_with(start, |node| {
+        seen.push(node.index);
+        visit(node.index)
+    });
+
+    seen
+}
+
+#[test]
+fn iterators_visit_in_order() {
+    let tree = two_levels();
+    let pre: Vec<usize> = tree.pre_order().map(|node| node.index).collect();
+    let post: Vec<usize> = tree.post_order().map(|node| node.index).collect();
+    let breadth: Vec<usize> = tree.breadth_first().map(|node| node.index).collect();
+    assert_eq!(pre, vec![0, 1, 2, 5, 6, 7, 8, 3, 4]);
+    assert_eq!(post, vec![1, 5, 6, 7, 8, 2, 3, 4, 0]);
+    assert_eq!(breadth, vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn node_refs_describe_the_node() {
+    let tree = two_levels();
+    let root = tree.node(0);
+    assert_eq!((root.depth, root.children.clone(), root.items), (0, Some(1..5), &[][..]));
+    assert!(!root.is_leaf());
+
+    let stem = tree.node(2);
+    assert_eq!((stem.depth, stem.children), (1, Some(5..9)));
+    assert_eq!(stem.boundary.min, Vec2::ZERO);
+    assert_eq!(stem.boundary.max, Vec2::splat(50.));
+
+    let leaf = tree.node(4);
+    assert!(leaf.is_leaf());
+    assert_eq!((leaf.depth, leaf.items), (1, &[4][..]));
+    assert!(tree.node(1).items.is_empty());
+    assert_eq!(
+        tree.breadth_first().map(|node| node.depth).collect::<Vec<_>>(),
+        vec![0, 1, 1, 1, 1, 2, 2, 2, 2]
+    );
+}
+
+#[test]
+fn walk_follows_the_visitor() {
+    let tree = two_levels();
+    assert_eq!(walked(&tree, 0, |_| Visit::Descend), vec![0, 1, 2, 5, 6, 7, 8, 3, 4]);
+
+    // pruning the stem skips its children but keeps going with its siblings
+    let prune_stem = |index| if index == 2 { Visit::Prune } else { Visit::Descend };
+    assert_eq!(walked(&tree, 0, prune_stem), vec![0, 1, 2, 3, 4]);
+    assert_eq!(walked(&tree, 0, |_| Visit::Prune), vec![0]);
+
+    // stopping ends the walk on the spot, nothing after it is seen
+    let stop_early = |index| if index == 6 { Visit::Stop } else { Visit::Descend };
+    assert_eq!(walked(&tree, 0, stop_early), vec![0, 1, 2, 5, 6]);
+    assert_eq!(walked(&tree, 0, |_| Visit::Stop), vec![0]);
+
+    assert_eq!(walked(&tree, 2, |_| Visit::Descend), vec![2, 5, 6, 7, 8]);
+    assert_eq!(walked(&tree, 4, |_| Visit::Descend), vec![4]);
+
+    let mut leaves = LeafCollector(Vec::new());
+    tree.walk(&mut leaves);
+    assert_eq!(leaves.0, vec![1, 5, 6, 7, 8, 3, 4]);
+}
+
+#[test]
+fn every_reachable_node_once() {
+    fastrand::seed(221);
+    let points = random_points(3000, Vec2::ZERO, Vec2::splat(100.));
+    let mut tree = QuadTree::build(4, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+
+    // collapsing a corner leaves node groups behind that no walk should reach
+    (0..points.len()).filter(|&index| points[index].x < 40. && points[index].y < 40.).for_each(|index| {
+        assert!(tree.remove(index, &points));
+    });
+    assert!(!tree.free_node_groups.is_empty());
+
+    let mut reachable = Vec::new();
+    let mut stack = vec![QuadTree::ROOT_INDEX];
+    while let Some(node_index) = stack.pop() {
+        reachable.push(node_index);
+        stack.extend(
+            tree.nodes[node_index]
+                .leaves
+                .map(|leaf_start| leaf_start..(leaf_start + 4))
+                .into_iter()
+                .flatten(),
+        );
+    }
+    let reachable = sorted(reachable);
+    assert!(reachable.len() < tree.nodes.len());
+
+    assert_eq!(sorted(tree.pre_order().map(|node| node.index).collect()), reachable);
+    assert_eq!(sorted(tree.post_order().map(|node| node.index).collect()), reachable);
+    assert_eq!(sorted(tree.breadth_first().map(|node| node.index).collect()), reachable);
+    assert_eq!(sorted(walked(&tree, 0, |_| Visit::Descend)), reachable);
+
+    // parents come first in pre order and last in post order
+    let position = |order: &[usize], index: usize| order.iter().position(|&node| node == index).unwrap();
+    let pre: Vec<usize> = tree.pre_order().map(|node| node.index).collect();
+    let post: Vec<usize> = tree.post_order().map(|node| node.index).collect();
+    let depths: Vec<usize> = tree.breadth_first().map(|node| node.depth).collect();
+    assert!(depths.windows(2).all(|pair| pair[0] <= pair[1]));
+    tree.pre_order().for_each(|node| {
+        node.children.into_iter().flatten().for_each(|child| {
+            assert!(position(&pre, node.index) < position(&pre, child));
+            assert!(position(&post, node.index) > position(&post, child));
+        });
+    });
+}
@@ -0,0 +1,258 @@
+use glam::Vec2;
+use quadtree::BoundingBox;
+use quadtree::DuplicatePolicy;
+use quadtree::OutOfBoundsPolicy;
+use quadtree::QuadTree;
+use quadtree::TreeViolation;
+
+/// one point in each quadrant of the root, so it is a single stem over 4
+/// leaves holding one item each
+fn one_level() -> (QuadTree, Vec<Vec2>) {
+    let points = vec![Vec2::new(25., 25.), Vec2::new(75., 25.), Vec2::new(25., 75.), Vec2::new(75., 75.)];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+    assert_eq!(tree.nodes.len(), 5);
+
+    (tree, points)
+}
+
+/// the lower left quadrant is split again, two of the others are empty
+fn two_levels() -> (QuadTree, Vec<Vec2>) {
+    let points = vec![
+        Vec2::new(10., 10.),
+        Vec2::new(40., 10.),
+        Vec2::new(10., 40.),
+        Vec2::new(40., 40.),
+        Vec2::new(75., 75.),
+    ];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)));
+    tree.construct_tree(&points);
+    assert_eq!(tree.nodes.len(), 9);
+
+    (tree, points)
+}
+
+fn violations(tree: &QuadTree, points: &[Vec2]) -> Vec<TreeViolation> {
+    tree.validate(points).map_or_else(|error| error.violations, |_| Vec::new())
+}
+
+fn stored_violations(tree: &QuadTree, points: &[Vec2], expected: &[usize]) -> Vec<TreeViolation> {
+    tree.validate_stored(points, expected).map_or_else(|error| error.violations, |_| Vec::new())
+}
+
+fn leaf_of(tree: &QuadTree, point: Vec2) -> usize {
+    tree.find_leaf(point).unwrap()
+}
+
+#[test]
+fn removed_items_are_not_missing() {
+    let (mut tree, points) = two_levels();
+    assert!(tree.remove(1, &points));
+    assert_eq!(tree.validate(&points), Ok(()));
+    assert_eq!(stored_violations(&tree, &points, &[0, 2, 3, 4]), vec![]);
+    assert_eq!(
+        stored_violations(&tree, &points, &[0, 1, 2, 3, 4]),
+        vec![TreeViolation::MissingItem { item: 1 }]
+    );
+    assert_eq!(
+        stored_violations(&tree, &points, &[2, 3, 4]),
+        vec![TreeViolation::UnexpectedItem { item: 0 }]
+    );
+}
+
+#[test]
+fn left_out_items_are_not_missing() {
+    let points = vec![Vec2::splat(10.), Vec2::splat(10.), Vec2::splat(150.), Vec2::splat(60.)];
+    let everything = [0, 1, 2, 3];
+    let boundary = BoundingBox::build(Vec2::ZERO, Vec2::splat(100.));
+
+    // the coincident item and the one outside of the root were never stored
+    let mut tree = QuadTree::build(1, boundary).with_duplicate_policy(DuplicatePolicy::Discard);
+    tree.construct_tree(&points);
+    assert_eq!(stored_violations(&tree, &points, &everything), vec![]);
+
+    let mut tree = QuadTree::build(1, boundary).with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow);
+    tree.construct_tree(&points);
+    assert_eq!(stored_violations(&tree, &points, &everything), vec![]);
+    tree.overflow.clear();
+    assert_eq!(stored_violations(&tree, &points, &everything), vec![TreeViolation::MissingItem { item: 2 }]);
+}
+
+#[test]
+fn expected_items_past_the_end() {
+    let (tree, points) = one_level();
+    assert_eq!(
+        stored_violations(&tree, &points, &[0, 1, 2, 3, 7]),
+        vec![TreeViolation::ItemIndexOutOfRange { item: 7 }]
+    );
+}
+
+#[test]
+fn node_count() {
+    let (mut tree, points) = one_level();
+    let extra = tree.nodes[1];
+    tree.nodes.push(extra);
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::NodeCount { len: 6 }]);
+}
+
+#[test]
+fn leaves_out_of_range() {
+    let (mut tree, points) = one_level();
+    tree.nodes[QuadTree::ROOT_INDEX].leaves = Some(3);
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::LeavesOutOfRange { stem: 0, leaf_start: 3 }]);
+}
+
+#[test]
+fn node_reached_twice() {
+    // an empty leaf turned into a copy of the split quadrant, sharing its
+    // children. not the first child, which the split is read from
+    let (mut tree, points) = two_levels();
+    let stem = (1..5).find(|&node_index| tree.nodes[node_index].leaves.is_some()).unwrap();
+    let empty = (2..5).rev().find(|&node_index| tree.leaf_items(node_index).is_empty()).unwrap();
+    tree.nodes[empty] = tree.nodes[stem];
+
+    let mut expected = vec![TreeViolation::ChildBoundary { stem: 0, child: empty }];
+    expected.extend((5..9).map(|node| TreeViolation::NodeReachedTwice { node }));
+    assert_eq!(violations(&tree, &points), expected);
+}
+
+#[test]
+fn child_boundary() {
+    let (mut tree, points) = one_level();
+    tree.nodes[3].boundary.max.x += 1.;
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::ChildBoundary { stem: 0, child: 3 }]);
+}
+
+#[test]
+fn child_depth() {
+    let (mut tree, points) = one_level();
+    tree.nodes[2].depth = 3;
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::ChildDepth { stem: 0, child: 2 }]);
+}
+
+#[test]
+fn stem_holds_items() {
+    let (mut tree, points) = one_level();
+    tree.nodes[QuadTree::ROOT_INDEX].data_head = Some(0);
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::StemHoldsItems { stem: 0 }]);
+}
+
+#[test]
+fn item_run_out_of_range() {
+    let (mut tree, points) = one_level();
+    let leaf = leaf_of(&tree, points[2]);
+    tree.nodes[leaf].data_len = 10;
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::ItemRunOutOfRange { leaf }]);
+}
+
+#[test]
+fn item_index_out_of_range() {
+    let (mut tree, points) = one_level();
+    let leaf = leaf_of(&tree, points[1]);
+    let slot = tree.nodes[leaf].data_head.unwrap();
+    tree.item_indices[slot] = 9;
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::ItemIndexOutOfRange { item: 9 }]);
+}
+
+#[test]
+fn item_outside_leaf() {
+    // two leaves trading their items
+    let (mut tree, points) = one_level();
+    let first = leaf_of(&tree, points[0]);
+    let second = leaf_of(&tree, points[3]);
+    let first_slot = tree.nodes[first].data_head.unwrap();
+    let second_slot = tree.nodes[second].data_head.unwrap();
+    tree.item_indices.swap(first_slot, second_slot);
+
+    let mut expected = vec![
+        TreeViolation::ItemOutsideLeaf { leaf: first, item: 3 },
+        TreeViolation::ItemOutsideLeaf { leaf: second, item: 0 },
+    ];
+    expected.sort_by_key(|violation| match violation {
+        TreeViolation::ItemOutsideLeaf { leaf, .. } => *leaf,
+        _ => unreachable!(),
+    });
+    assert_eq!(violations(&tree, &points), expected);
+}
+
+#[test]
+fn overflow_inside_root_and_duplicates() {
+    let (mut tree, points) = one_level();
+    tree.overflow.push(2);
+    assert_eq!(
+        violations(&tree, &points),
+        vec![TreeViolation::DuplicateItem { item: 2 }, TreeViolation::OverflowInsideRoot { item: 2 },]
+    );
+
+    let points = vec![Vec2::splat(20.), Vec2::splat(-20.)];
+    let mut tree = QuadTree::build(1, BoundingBox::build(Vec2::ZERO, Vec2::splat(100.)))
+        .with_out_of_bounds_policy(OutOfBoundsPolicy::Overflow);
+    tree.construct_tree(&points);
+    assert_eq!(tree.validate_stored(&points, &[0, 1]), Ok(()));
+    tree.overflow.push(1);
+    assert_eq!(violations(&tree, &points), vec![TreeViolation::DuplicateItem { item: 1 }]);
+}
+
+#[test]
+fn error_lists_every_violation() {
+    let (mut tree, points) = one_level();
+    tree.nodes[2].depth = 3;
+    tree.nodes[3].depth = 3;
+    let error = tree.validate(&points).unwrap_err();
+    assert_eq!(error.violations.len(), 2);
+    assert_eq!(
+        error.to_string(),
+        "2 quadtree invariants are broken\n  child 2 isn't one level deeper than stem 0\n  child 3 isn't one \
+         level deeper than stem 0"
+    );
+}
+
+#[test]
+fn dump_text_lists_every_node() {
+    let (mut tree, points) = two_levels();
+    assert_eq!(
+        tree.dump_text(),
+        "0 (0, 0) .. (100, 100) stem\n  1 (50, 0) .. (100, 50) leaf []\n  2 (0, 0) .. (50, 50) stem\n    5 (25, 0) \
+         .. (50, 25) leaf [1]\n    6 (0, 0) .. (25, 25) leaf [0]\n    7 (0, 25) .. (25, 50) leaf [2]\n    8 (25, \
+         25) .. (50, 50) leaf [3]\n  3 (0, 50) .. (50, 100) leaf []\n  4 (50, 50) .. (100, 100) leaf [4]\noverflow \
+         []\n"
+    );
+
+    // the detached children of a collapsed stem don't show up
+    assert!(tree.remove(0, &points));
+    assert!(tree.remove(1, &points));
+    assert!(tree.remove(2, &points));
+    assert_eq!(
+        tree.dump_text(),
+        "0 (0, 0) .. (100, 100) stem\n  1 (50, 0) .. (100, 50) leaf []\n  2 (0, 0) .. (50, 50) leaf [3]\n  3 (0, \
+         50) .. (50, 100) leaf []\n  4 (50, 50) .. (100, 100) leaf [4]\noverflow []\n"
+    );
+}
+
+#[test]
+fn to_dot_has_a_box_per_node_and_an_edge_per_child() {
+    let (mut tree, mut points) = two_levels();
+    let count = |dot: &str, pattern: &str| dot.lines().filter(|line| line.contains(pattern)).count();
+
+    let dot = tree.to_dot();
+    assert!(dot.starts_with("digraph quadtree {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert_eq!(count(&dot, " [label="), 9);
+    assert_eq!(count(&dot, " -> "), 8);
+    assert_eq!(count(&dot, "\\n1 items\""), 5);
+    assert_eq!(count(&dot, "\\n0 items\""), 4);
+    assert!(dot.contains("    n2 -> n5;\n    n2 -> n6;\n    n2 -> n7;\n    n2 -> n8;\n"));
+    assert!(dot.contains("    n4 [label=\"4 depth 1\\n(50, 50) .. (100, 100)\\n1 items\"];\n"));
+
+    // overflow gets a box of its own, but no edges
+    tree.out_of_bounds_policy = OutOfBoundsPolicy::Overflow;
+    points.push(Vec2::new(150., 50.));
+    tree.construct_tree(&points);
+    assert!(tree.remove(0, &points));
+    assert!(tree.remove(1, &points));
+    assert!(tree.remove(2, &points));
+    let dot = tree.to_dot();
+    assert_eq!(count(&dot, " [label="), 6);
+    assert_eq!(count(&dot, " -> "), 4);
+    assert!(dot.contains("    overflow [label=\"overflow\\n1 items\"];\n"));
+}